@@ -0,0 +1,141 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Writes `contents` to `path` so that the file holds either its old or its new content,
+/// never a mix: the data goes to a temporary file in the same directory, is synced to disk
+/// and then renamed over `path`.
+///
+/// An existing file keeps its permissions, and a symlink is written through to its target.
+/// A read-only file is refused with `PermissionDenied`, as a plain write would be.
+pub fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_with(path, contents, false)
+}
+
+/// Like `write`, but also replaces a read-only file, which stays read-only. For `--force`.
+pub fn write_forced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_with(path, contents, true)
+}
+
+fn write_with(path: &Path, contents: &[u8], replace_readonly: bool) -> io::Result<()> {
+    let path = resolve_symlink(path)?;
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let permissions = match fs::metadata(&path) {
+        Ok(metadata) => Some(metadata.permissions()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+    // The rename would replace the file regardless of its permissions
+    if !replace_readonly && permissions.as_ref().is_some_and(|permissions| permissions.readonly()) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("{} is read-only", path.display()),
+        ));
+    }
+
+    let (temp_path, mut file) = create_temp(&dir, &path)?;
+    let result = (|| {
+        file.write_all(contents)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp_path, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    sync_dir(&dir);
+    Ok(())
+}
+
+/// The file a symlink points to, so the rename replaces the target rather than the link.
+fn resolve_symlink(path: &Path) -> io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => match fs::canonicalize(path) {
+            Ok(target) => Ok(target),
+            // A dangling link: its target is created, as a plain write would
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let target = fs::read_link(path)?;
+                resolve_symlink(&path.parent().unwrap_or(Path::new("")).join(target))
+            }
+            Err(e) => Err(e),
+        },
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Creates a new hidden file next to `path`, e.g. `.main.rs.tempo-1234-0.tmp`.
+fn create_temp(dir: &Path, path: &Path) -> io::Result<(PathBuf, File)> {
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or("file");
+    for attempt in 0.. {
+        let temp_path = dir.join(format!(".{file_name}.tempo-{}-{attempt}.tmp", std::process::id()));
+        match OpenOptions::new().write(true).create_new(true).open(&temp_path) {
+            Ok(file) => return Ok((temp_path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("the attempt counter is unbounded")
+}
+
+/// Makes the rename itself durable. Best effort: not every platform can sync a directory.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_refuses_read_only_files_unless_forced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run.sh");
+        fs::write(&path, "old").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        assert_eq!(write(&path, b"new").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        write_forced(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        // Only the file itself is left in the directory
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        write(&dir.path().join("fresh.txt"), b"x").unwrap();
+        assert_eq!(fs::read_to_string(dir.path().join("fresh.txt")).unwrap(), "x");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_goes_through_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+        fs::write(&target, "old").unwrap();
+        std::os::unix::fs::symlink("target.txt", &link).unwrap();
+
+        write(&link, b"new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+
+        // A dangling link gets its target created
+        fs::remove_file(&target).unwrap();
+        write(&link, b"again").unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "again");
+    }
+}
@@ -0,0 +1,167 @@
+use crate::atomic;
+use crate::error::AppError;
+use crate::manifest::{self, Manifest, TemplateEntry};
+use crate::scaffold;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path};
+
+const MANIFEST_PATH: &str = "manifest.toml";
+const TEMPLATES_PREFIX: &str = "templates/";
+
+/// Templates packed to move them to another store: a gzipped tar holding a
+/// `manifest.toml` with their entries and their files under `templates/`, laid out as in
+/// a store.
+#[derive(Debug)]
+pub struct Bundle {
+    pub manifest: Manifest,
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl Bundle {
+    /// The content of a file stored under `templates/`, by its path relative to it.
+    pub fn file(&self, relative_path: &str) -> Option<&[u8]> {
+        self.files.get(relative_path).map(Vec::as_slice)
+    }
+}
+
+/// The stored paths of a template's files, relative to `templates/`.
+pub fn stored_files(entry: &TemplateEntry) -> Vec<String> {
+    if entry.is_directory() {
+        entry.files.iter().map(|file| format!("{}/{file}", entry.filename_in_storage)).collect()
+    } else {
+        vec![entry.filename_in_storage.clone()]
+    }
+}
+
+/// Packs `templates`, each given as its name, its entry and the templates directory of
+/// its store, into a bundle at `path`. Revisions stay behind: their contents are not part
+/// of the bundle.
+///
+/// Returns the number of files packed.
+pub fn write(path: &Path, templates: &[(&str, &TemplateEntry, &Path)]) -> Result<usize, AppError> {
+    let mut fragment = Manifest::new();
+    for (name, entry, _) in templates {
+        let mut entry = (*entry).clone();
+        entry.history.clear();
+        fragment.templates.insert(name.to_string(), entry);
+    }
+    let manifest_toml = toml::to_string_pretty(&fragment).map_err(|e| AppError::Unexpected(e.to_string()))?;
+
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append(&mut builder, MANIFEST_PATH, manifest_toml.as_bytes(), chrono::Utc::now().timestamp())?;
+    let mut count = 0;
+    for (name, entry, templates_dir) in templates {
+        for file in stored_files(entry) {
+            let content = fs::read(scaffold::join(templates_dir, &file)).map_err(|_| AppError::TemplateFileMissing {
+                name: name.to_string(),
+                path: scaffold::join(templates_dir, &file),
+            })?;
+            append(&mut builder, &format!("{TEMPLATES_PREFIX}{file}"), &content, entry.updated_at.timestamp())?;
+            count += 1;
+        }
+    }
+
+    // Built in memory and written in one go, so a failed export leaves no partial bundle
+    let archive = builder.into_inner()?.finish()?;
+    atomic::write(path, &archive)?;
+    Ok(count)
+}
+
+fn append(builder: &mut tar::Builder<GzEncoder<Vec<u8>>>, path: &str, content: &[u8], mtime: i64) -> Result<(), AppError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime.max(0) as u64);
+    header.set_entry_type(tar::EntryType::Regular);
+    builder.append_data(&mut header, path, content)?;
+    Ok(())
+}
+
+/// Reads the bundle at `path`.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidBundle` if it is not a tempo bundle, has no manifest, or holds
+/// a path leading outside of it.
+pub fn read(path: &Path) -> Result<Bundle, AppError> {
+    let invalid = |reason: String| AppError::InvalidBundle { path: path.to_path_buf(), reason };
+    let mut archive = tar::Archive::new(GzDecoder::new(File::open(path)?));
+    let mut manifest = None;
+    let mut files = HashMap::new();
+
+    for entry in archive.entries().map_err(|e| invalid(e.to_string()))? {
+        let mut entry = entry.map_err(|e| invalid(e.to_string()))?;
+        if entry.header().entry_type() != tar::EntryType::Regular {
+            continue;
+        }
+        let entry_path = entry.path().map_err(|e| invalid(e.to_string()))?.into_owned();
+        if !entry_path.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(invalid(format!("it holds the path {entry_path:?}, which leads outside of it")));
+        }
+        let entry_path = entry_path
+            .to_str()
+            .ok_or_else(|| invalid(format!("{entry_path:?} is not a UTF-8 path")))?
+            .replace('\\', "/");
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content).map_err(|e| invalid(e.to_string()))?;
+
+        if entry_path == MANIFEST_PATH {
+            let text = String::from_utf8_lossy(&content);
+            let (parsed, _) = manifest::parse(&text).map_err(|e| match e {
+                manifest::FormatError::Toml(e) => invalid(format!("its manifest does not parse: {}", e.message())),
+                manifest::FormatError::TooNew(version) => {
+                    invalid(format!("it was made by a newer tempo (manifest format {version})"))
+                }
+            })?;
+            manifest = Some(parsed);
+        } else if let Some(relative_path) = entry_path.strip_prefix(TEMPLATES_PREFIX) {
+            files.insert(relative_path.to_string(), content);
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| invalid(format!("it has no {MANIFEST_PATH}")))?;
+    Ok(Bundle { manifest, files })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+
+    #[test]
+    fn test_write_and_read_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates_dir = dir.path().join("templates");
+        fs::create_dir_all(templates_dir.join("cp")).unwrap();
+        fs::create_dir_all(templates_dir.join("cli").join("src")).unwrap();
+        fs::write(templates_dir.join("cp").join("segtree.cpp"), "struct T;").unwrap();
+        fs::write(templates_dir.join("cli").join("src").join("main.rs"), "fn main() {}").unwrap();
+
+        let mut manifest = Manifest::new();
+        let mut segtree = TemplateEntry::new("cp/segtree.cpp".to_string(), "cpp".to_string());
+        segtree.tags = vec!["cp".to_string()];
+        manifest.add_template("cp/segtree".to_string(), segtree.clone());
+        let mut cli = TemplateEntry::new("cli".to_string(), String::new());
+        cli.files = vec!["src/main.rs".to_string()];
+        manifest.add_template("cli".to_string(), cli);
+
+        let path = dir.path().join("bundle.tar.gz");
+        let templates: Vec<(&str, &TemplateEntry, &Path)> =
+            manifest.templates.iter().map(|(name, entry)| (name.as_str(), entry, templates_dir.as_path())).collect();
+        assert_eq!(write(&path, &templates).unwrap(), 2);
+        let bundle = read(&path).unwrap();
+        let read_back = bundle.manifest.get_template("cp/segtree").unwrap();
+        assert_eq!(read_back.tags, segtree.tags);
+        assert_eq!(read_back.created_at, segtree.created_at.with_nanosecond(0).unwrap());
+        assert_eq!(bundle.file("cp/segtree.cpp"), Some(&b"struct T;"[..]));
+        assert_eq!(bundle.file("cli/src/main.rs"), Some(&b"fn main() {}"[..]));
+
+        fs::write(dir.path().join("not-a-bundle.tar.gz"), "hello").unwrap();
+        assert!(matches!(read(&dir.path().join("not-a-bundle.tar.gz")), Err(AppError::InvalidBundle { .. })));
+    }
+}
@@ -1,6 +1,9 @@
-use clap::{ArgAction, Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
+use crate::manifest::WriteStrategy;
+use crate::template;
+
 /// `tempo`: The Code Templating Assistant
 /// Quickly manage and use code templates for various purposes.
 #[derive(Parser, Debug)]
@@ -20,6 +23,10 @@ pub struct Cli {
     /// Suppress all output except for errors and essential data (like list/show output)
     #[arg(short, long, global = true, default_value_t = false, group = "verbosity", action = ArgAction::SetTrue)]
     pub quiet: bool,
+
+    /// Use this directory as the template store instead of `TEMPO_HOME` or ~/.config/tempo
+    #[arg(long, global = true, value_name = "DIR")]
+    pub store: Option<PathBuf>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -46,14 +53,48 @@ pub enum Commands {
 
     /// Print the full path to a specified template
     Path(PathArgs),
+
+    /// Revert the most recent applies, restoring the files they changed
+    Undo(UndoArgs),
+
+    /// Check the template store for inconsistencies, and repair them with --fix
+    Doctor(DoctorArgs),
+
+    /// Rebuild the manifest from the files in the templates directory
+    Reindex(ReindexArgs),
+
+    /// List the earlier revisions of a template
+    History(HistoryArgs),
+
+    /// Roll a template back to an earlier revision
+    Restore(RestoreArgs),
+
+    /// Show how a template changed since an earlier revision
+    Diff(DiffArgs),
+
+    /// Set up the template store
+    Store(StoreArgs),
+
+    /// Pull template changes from the store's git remote and push local ones
+    Sync,
+
+    /// Pack templates with their metadata into a bundle to share
+    Export(ExportArgs),
+
+    /// Add the templates of a bundle made with `tempo export`
+    Import(ImportArgs),
 }
 
 #[derive(Args, Debug)]
 pub struct AddArgs {
     /// The name to assign to the new template
     pub name: String,
-    /// The path to the source file to the template
+    /// The path to the source file to the template, or to a directory to store as a whole
     pub source_file_path: PathBuf,
+
+    /// Store the template in the project's `.tempo/` store, creating one here if there is none
+    #[arg(short = 'l', long)]
+    pub local: bool,
 }
 
 /// Arguments for the `new` command
@@ -64,7 +105,7 @@ pub struct AddArgs {
 ///    - If --force is NOT given: error or skip (we'll define this default behavior).
 ///  > **This means 'overwrite' can be triggered by -o OR by (--force AND no -a/-p).**
 #[derive(Args, Debug)]
-#[group(args(&["overwrite", "append", "prepend"]))]
+#[group(args(&["overwrite", "append", "prepend", "after", "before", "at_marker"]))]
 pub struct ApplyArgs {
     /// Name of the template to use
     pub template_name: String,
@@ -83,6 +124,57 @@ pub struct ApplyArgs {
     /// Prepend template content to the destination file if it exists
     #[arg(short = 'p', long, group = "write_strategy")]
     pub prepend: bool,
+
+    /// Insert after the single line of the destination file matching this regex
+    #[arg(long, value_name = "REGEX", group = "write_strategy")]
+    pub after: Option<String>,
+
+    /// Insert before the single line of the destination file matching this regex
+    #[arg(long, value_name = "REGEX", group = "write_strategy")]
+    pub before: Option<String>,
+
+    /// Insert at the `tempo:insert <NAME>` marker line of the destination file
+    #[arg(long, value_name = "NAME", group = "write_strategy")]
+    pub at_marker: Option<String>,
+
+    /// Wrap the output in begin/end comments naming the template; applying again replaces that block
+    #[arg(long)]
+    pub managed: bool,
+
+    /// Show what would be written without changing any file
+    #[arg(short = 'n', long)]
+    pub dry_run: bool,
+
+    /// Print a unified diff between each destination file and its content after the apply
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Remove the template's managed block from the destination file instead of applying
+    #[arg(long, conflicts_with_all = ["write_strategy", "set", "managed"])]
+    pub remove: bool,
+
+    /// Set a value for a template variable, e.g. `--set name=Solver` (repeatable)
+    #[arg(short = 's', long = "set", value_name = "KEY=VALUE", value_parser = parse_key_val)]
+    pub set: Vec<(String, String)>,
+}
+
+impl ApplyArgs {
+    /// The write strategy chosen with `-o`, `-a`, `-p`, `--after`, `--before` or `--at-marker`, if any.
+    pub fn write_strategy(&self) -> Option<WriteStrategy> {
+        if self.overwrite {
+            Some(WriteStrategy::Overwrite)
+        } else if self.append {
+            Some(WriteStrategy::Append)
+        } else if self.prepend {
+            Some(WriteStrategy::Prepend)
+        } else if let Some(pattern) = &self.after {
+            Some(WriteStrategy::After(pattern.clone()))
+        } else if let Some(pattern) = &self.before {
+            Some(WriteStrategy::Before(pattern.clone()))
+        } else {
+            self.at_marker.clone().map(WriteStrategy::AtMarker)
+        }
+    }
 }
 
 #[derive(Args, Debug)]
@@ -96,6 +188,18 @@ pub struct RemoveArgs {
 pub struct ShowArgs {
     /// Name of the template to show
     pub template_name: String,
+
+    /// Render the template instead of printing its source
+    #[arg(short = 'r', long)]
+    pub render: bool,
+
+    /// Print the variables the template declares instead of its content
+    #[arg(long, conflicts_with = "render")]
+    pub params: bool,
+
+    /// Set a value for a template variable when rendering (repeatable)
+    #[arg(short = 's', long = "set", value_name = "KEY=VALUE", value_parser = parse_key_val, requires = "render")]
+    pub set: Vec<(String, String)>,
 }
 
 /// Arguments for the `edit` command
@@ -111,3 +215,145 @@ pub struct PathArgs {
     /// Name of the template whose path is to be shown
     pub template_name: String,
 }
+
+/// Arguments for the `undo` command
+#[derive(Args, Debug)]
+pub struct UndoArgs {
+    /// How many of the most recent applies to revert
+    #[arg(default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub count: u32,
+
+    /// List the recorded applies instead of reverting any
+    #[arg(short = 'l', long, conflicts_with = "count")]
+    pub list: bool,
+
+    /// Revert files edited since the apply too, losing those edits
+    #[arg(long, conflicts_with = "list")]
+    pub discard_changes: bool,
+}
+
+/// Arguments for the `doctor` command
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Prune entries whose files are gone and adopt stray files into the manifest
+    #[arg(long)]
+    pub fix: bool,
+}
+
+/// Arguments for the `reindex` command
+#[derive(Args, Debug)]
+pub struct ReindexArgs {
+    /// Reindex the project's `.tempo/` store instead of the global one
+    #[arg(short = 'l', long)]
+    pub local: bool,
+
+    /// Read this stored directory as a directory template rather than a namespace (repeatable)
+    #[arg(long = "dir", value_name = "PATH")]
+    pub directories: Vec<String>,
+}
+
+/// Arguments for the `history` command
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    /// Name of the template whose revisions to list
+    pub template_name: String,
+}
+
+/// Arguments for the `restore` command
+#[derive(Args, Debug)]
+pub struct RestoreArgs {
+    /// Name of the template to roll back
+    pub template_name: String,
+
+    /// The revision to restore, as numbered by `tempo history`
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub rev: u32,
+}
+
+/// Arguments for the `diff` command
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Name of the template to compare
+    pub template_name: String,
+
+    /// The revision to compare the current content with, as numbered by `tempo history`
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub rev: u32,
+}
+
+/// Arguments for the `store` command
+#[derive(Args, Debug)]
+pub struct StoreArgs {
+    #[command(subcommand)]
+    pub command: StoreCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StoreCommands {
+    /// Create the store, optionally as a git repository to sync with others
+    Init(StoreInitArgs),
+}
+
+/// Arguments for the `store init` command
+#[derive(Args, Debug)]
+pub struct StoreInitArgs {
+    /// Make the store a git repository, committing after each change
+    #[arg(long)]
+    pub git: bool,
+
+    /// The git remote `tempo sync` pulls from and pushes to
+    #[arg(long, value_name = "URL", requires = "git")]
+    pub remote: Option<String>,
+}
+
+/// Arguments for the `export` command
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// Names of the templates to export; all templates if neither names nor tags are given
+    pub names: Vec<String>,
+
+    /// Export the templates with this tag (repeatable)
+    #[arg(short, long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// The bundle to write, e.g. `templates.tar.gz`
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+}
+
+/// Arguments for the `import` command
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// The bundle to read, as written by `tempo export`
+    pub bundle: PathBuf,
+
+    /// What to do with a bundled template whose name is already taken
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Skip)]
+    pub on_conflict: ConflictPolicy,
+
+    /// Import into the project's `.tempo/` store, creating one here if there is none
+    #[arg(short = 'l', long)]
+    pub local: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ConflictPolicy {
+    /// Keep the existing template and leave the bundled one out
+    Skip,
+    /// Import the bundled template as `<name>-2` (or the next free number)
+    Rename,
+    /// Replace the existing template, keeping its content as a revision
+    Overwrite,
+}
+
+/// Parses a `KEY=VALUE` pair as given to `--set`.
+fn parse_key_val(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{s}'"))?;
+    let key = key.trim();
+    if !template::is_valid_name(key) {
+        return Err(format!("'{key}' is not a valid variable name"));
+    }
+    Ok((key.to_string(), value.to_string()))
+}
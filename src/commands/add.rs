@@ -1,8 +1,16 @@
 use crate::cli::AddArgs;
-use crate::config;
+use crate::config::{self, Stores};
 use crate::error::AppError;
+use crate::frontmatter;
+use crate::git;
+use crate::history;
+use crate::lock;
+use crate::namespace;
+use crate::scaffold;
+use crate::template;
 use colored::*;
 use std::fs;
+use std::io;
 use crate::manifest::TemplateEntry;
 use crate::output::OutputConfig;
 
@@ -15,7 +23,7 @@ use crate::output::OutputConfig;
 /// # Returns
 /// * `Ok(())` if the template was added successfully.
 /// * `Err(AppError)` if an error occurred.
-pub fn run(args: &AddArgs, force: bool, output: &OutputConfig) -> Result<(), AppError> {
+pub fn run(args: &AddArgs, force: bool, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
     output.info(
             format!("\t{} {} from {}...",
             "→ Adding template".blue().bold(),
@@ -37,28 +45,52 @@ pub fn run(args: &AddArgs, force: bool, output: &OutputConfig) -> Result<(), App
             args.source_file_path.clone(),
         ));
     }
-    if !args.source_file_path.is_file() {
+    // A directory is stored as a whole, as a template holding several files
+    let source_is_dir = args.source_file_path.is_dir();
+    if !source_is_dir && !args.source_file_path.is_file() {
         return Err(AppError::SourcePathIsNotAFile(
             args.source_file_path.clone(),
         ));
     }
 
-    // > Validate template name (basic validation for now)
-    //    TODO: A more robust validation might involve regex or checking for reserved names.
-    if args.name.contains('/') || args.name.contains('\\') {
-        return Err(AppError::TemplateNameInvalid(
-            args.name.clone(),
-            "Name cannot contain path separators".to_string(),
-        ));
+    // > Validate template name: `name`, or `namespace/name` to store it in a subdirectory
+    let template_name = namespace::parse(&args.name)
+        .map_err(|reason| AppError::TemplateNameInvalid(args.name.clone(), reason))?;
+    if let Some(namespace) = template_name.namespace {
+        output.verbose(format!("\t\t[VERBOSE] Template goes in namespace '{}'.", namespace));
     }
-    if args.name.trim().is_empty() {
-        return Err(AppError::TemplateNameInvalid(
-            args.name.clone(),
-            "Name cannot be empty".to_string(),
+
+    // > Read metadata from the template's front matter, if it has one, and the template it extends
+    //    (directories and templates that are not UTF-8 text cannot have either)
+    let (front_matter, parent) = match fs::read_to_string(&args.source_file_path) {
+        _ if source_is_dir => (None, None),
+        Ok(content) => {
+            let (front_matter, body) =
+                frontmatter::split(&content).map_err(|reason| AppError::InvalidFrontMatter {
+                    path: args.source_file_path.clone(),
+                    reason,
+                })?;
+            (front_matter, template::parent_of(body))
+        }
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => (None, None),
+        Err(e) => return Err(AppError::Io(e)),
+    };
+
+    // > Pick the store: the project's with --local (creating `.tempo/` here if there is none), else the global one
+    let store = &stores.target(args.local, output)?;
+    if !args.local
+        && let Some(local) = &stores.local
+        && local.load_manifest()?.get_template(&args.name).is_some()
+    {
+        output.warn(format!(
+            "\t\tWarning: The project store also has a template '{}', which is used instead of this one here.",
+            args.name
         ));
     }
 
-    let mut manifest = config::load_manifest()?;
+    // Held until the manifest is saved, so a concurrent command cannot lose this entry
+    let _lock = lock::acquire(store, output)?;
+    let mut manifest = store.load_manifest()?;
     output.verbose(format!("\t\t[VERBOSE] Manifest loaded. {} templates.", manifest.templates.len()));
 
     if manifest.get_template(&args.name).is_some() && !force {
@@ -66,14 +98,16 @@ pub fn run(args: &AddArgs, force: bool, output: &OutputConfig) -> Result<(), App
     }
     
     // > Get the templates storage directory
-    let templates_dir = config::get_templates_dir()?;
+    let templates_dir = store.templates_dir()?;
 
     // > Construct the destination path
-    //    We want to store it as `<name>.<original_extension>`
+    //    We want to store it as `<name>.<original_extension>`, under a subdirectory per namespace
+    //    Directories are stored under the bare name
     let original_extension = args
         .source_file_path
         .extension()
         .and_then(|ext| ext.to_str())
+        .filter(|_| !source_is_dir)
         .unwrap_or("")// Fallback to empty string if no extension
         .to_lowercase(); 
 
@@ -83,7 +117,29 @@ pub fn run(args: &AddArgs, force: bool, output: &OutputConfig) -> Result<(), App
         dest_filename.push_str(&original_extension);
     }
 
-    let dest_path = templates_dir.join(dest_filename.clone()); // Use `dest_filename` here as it's now owned
+    let dest_path = scaffold::join(&templates_dir, &dest_filename);
+
+    // > A namespace cannot share its directory with a template stored under the same path
+    if let Some((other, _)) = manifest.templates.iter().find(|(other, entry)| {
+        *other != &args.name && namespace::storage_overlaps(&entry.filename_in_storage, &dest_filename)
+    }) {
+        return Err(AppError::TemplateNameInvalid(
+            args.name.clone(),
+            format!("its storage path '{dest_filename}' clashes with template '{other}'"),
+        ));
+    }
+
+    // > List the files of a directory, leaving out ignored paths
+    let files = if source_is_dir {
+        let files = scaffold::collect_files(&args.source_file_path)?;
+        if files.is_empty() {
+            return Err(AppError::EmptyDirectoryTemplate(args.source_file_path.clone()));
+        }
+        output.verbose(format!("\t\t[VERBOSE] Directory holds {} file(s) to store.", files.len()));
+        files
+    } else {
+        Vec::new()
+    };
 
     output.info(
         format!(
@@ -98,36 +154,72 @@ pub fn run(args: &AddArgs, force: bool, output: &OutputConfig) -> Result<(), App
         return Err(AppError::TemplateAlreadyExists(args.name.clone()));
     }
 
-    if force && manifest.get_template(&args.name).is_some() {
-        if let Some(existing_entry) = manifest.get_template(&args.name) {
-            let old_file_path = templates_dir.join(&existing_entry.filename_in_storage);
-            if old_file_path.exists() && old_file_path != dest_path { 
-                output.verbose(format!("\t\t[VERBOSE] Removing old file due to overwrite: {:?}", old_file_path));
-                fs::remove_file(&old_file_path).map_err(|e| AppError::FileRemove {
-                    path: old_file_path,
-                    source_error: e,
-                })?;
-            }
+    let mut history = Vec::new();
+    if force
+        && let Some(existing_entry) = manifest.get_template(&args.name)
+    {
+        // Keep the content being replaced as a revision, so `tempo restore` can bring it back
+        history = existing_entry.history.clone();
+        if let Some(revision) = history::snapshot(store, existing_entry, &templates_dir)? {
+            output.verbose(format!("\t\t[VERBOSE] Previous content kept as revision {}.", history.len() + 1));
+            history.push(revision);
+        }
+
+        let old_file_path = existing_entry.stored_path(&templates_dir);
+        // A stored directory is replaced as a whole, so no stale files are left in it
+        if old_file_path.exists() && (old_file_path != dest_path || old_file_path.is_dir()) {
+            output.verbose(format!("\t\t[VERBOSE] Removing old file due to overwrite: {:?}", old_file_path));
+            scaffold::remove_stored(&old_file_path).map_err(|e| AppError::FileRemove {
+                path: old_file_path,
+                source_error: e,
+            })?;
         }
     }
 
-    fs::copy(&args.source_file_path, &dest_path).map_err(|e| AppError::FileCopy {
-        from: args.source_file_path.clone(),
-        to: dest_path.clone(),
-        source_error: e,
-    })?;
+    if let Some(parent) = dest_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if source_is_dir {
+        scaffold::copy_files(&args.source_file_path, &dest_path, &files)?;
+    } else {
+        fs::copy(&args.source_file_path, &dest_path).map_err(|e| AppError::FileCopy {
+            from: args.source_file_path.clone(),
+            to: dest_path.clone(),
+            source_error: e,
+        })?;
+    }
 
     // --- Create and add TemplateEntry to Manifest ---
     let mut new_entry = TemplateEntry::new(dest_filename.clone(), original_extension.clone());
     new_entry.original_source_path = Some(args.source_file_path.clone().canonicalize().unwrap_or_else(|_| args.source_file_path.clone()));
-    // TODO: any other metadata like description, tags could be set here 
+    new_entry.files = files;
+    new_entry.history = history;
+    if let Some(front_matter) = &front_matter {
+        front_matter.apply_to(&mut new_entry);
+        output.verbose(format!(
+            "\t\t[VERBOSE] Front matter found: {} tag(s), {} variable(s).",
+            front_matter.tags.len(),
+            front_matter.variables.len()
+        ));
+    }
+    if let Some(parent) = parent {
+        if manifest.get_template(&parent).is_none() && config::find_template(&stores.load()?, &parent).is_none() {
+            output.warn(format!(
+                "\t\tWarning: Template '{}' extends '{}', which does not exist yet.",
+                args.name, parent
+            ));
+        }
+        output.verbose(format!("\t\t[VERBOSE] Template extends '{}'.", parent));
+        new_entry.extends = Some(parent);
+    }
 
     manifest.add_template(args.name.clone(), new_entry);
     output.verbose(format!("\t\t[VERBOSE] Template entry for '{}' added/updated in manifest.", args.name));
     
     // --- Save Manifest ---
-    config::save_manifest(&manifest)?;
+    store.save_manifest(&manifest)?;
     output.verbose(format!("\t\t[VERBOSE] Manifest saved. Total templates: {}.", manifest.templates.len()));
+    git::autocommit(store.root_path(), &format!("tempo add {}", args.name), output);
 
     output.success(format!(
         "\t{} Template '{}' added successfully.",
@@ -1,13 +1,31 @@
+use crate::atomic;
 use crate::cli::ApplyArgs;
-use crate::config;
-use crate::error::AppError;
+use crate::config::{self, Store, Stores};
+use crate::context;
+use crate::diff;
+use crate::error::{self, AppError};
+use crate::frontmatter;
+use crate::insert::{self, Anchor, InsertError};
+use crate::journal::{Change, Journal};
+use crate::loader::StoreLoader;
+use crate::lock;
+use crate::managed::{self, BlockError};
+use crate::manifest::{TemplateEntry, WriteStrategy};
 use crate::output::OutputConfig;
+use crate::prompt;
+use crate::scaffold;
+use crate::schema::VariableSpec;
 use colored::*;
-use std::fs::{self, File, OpenOptions};
-use std::io::{Read, Write};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
 /// Handles the `tempo new` command.
-pub fn run(args: &ApplyArgs, force: bool, output: &OutputConfig) -> Result<(), AppError> {
+pub fn run(args: &ApplyArgs, force: bool, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    if args.remove {
+        return remove_managed(args, force, stores, output);
+    }
+
     output.info(
         format!("\n\t{} template {} to {}...",
         "→ Applying".blue().bold(),
@@ -16,86 +34,604 @@ pub fn run(args: &ApplyArgs, force: bool, output: &OutputConfig) -> Result<(), A
     ));
 
     // 1. Find the template
-    let manifest = config::load_manifest()?;
+    let layers = stores.load()?;
     output.verbose("[VERBOSE] Manifest loaded for 'apply' command.");
 
-    let template_entry = match manifest.get_template(&args.template_name) {
-        Some(entry) => entry,
+    let (layer, template_entry) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
         None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
     };
 
-    let filename_in_storage = &template_entry.filename_in_storage;
-    let templates_dir = config::get_templates_dir()?;
-    let template_file_path = templates_dir.join(filename_in_storage);
+    let template_file_path = template_entry.stored_path(&layer.templates_dir);
     output.verbose(
         format!("\t\t{} Using template file: {}",
                 "[VERBOSE]".magenta(),
         format!("{template_file_path:?}").cyan()
     ));
 
-    // 2. Read template content
-    let template_content = fs::read_to_string(&template_file_path).map_err(|e| AppError::Io(e))?;
+    output.verbose(format!("\t\t[VERBOSE] Template found in the {} store.", layer.layer));
+
+    // The journal of applies lives in the global store, whichever store the template comes from
+    let store = &stores.global;
+    let loader = StoreLoader::new(&layers);
+    if template_entry.is_directory() {
+        return apply_directory(args, force, store, template_entry, &template_file_path, &loader, output);
+    }
+
+    // 2. Read template content and separate its front matter
+    let template_source = fs::read_to_string(&template_file_path).map_err(AppError::Io)?;
     // Consider adding a specific AppError variant for template read failure if needed
+    let (front_matter, template_body) =
+        frontmatter::split(&template_source).map_err(|reason| AppError::InvalidFrontMatter {
+            path: template_file_path.clone(),
+            reason,
+        })?;
+    // The file's own front matter wins over the copy in the manifest
+    let variables = front_matter
+        .as_ref()
+        .map_or(&template_entry.variables, |fm| &fm.variables);
+    let default_strategy = front_matter
+        .as_ref()
+        .map_or(&template_entry.default_strategy, |fm| &fm.strategy);
+
+    // 3. Render placeholders: built-in context first, then the values given via --set
+    //    Anything still missing is asked for on the terminal
+    let mut vars =
+        context::template_variables(&args.template_name, template_entry, &args.destination_file_path, &args.set);
+    prompt::resolve_declared(&args.template_name, variables, &mut vars, output)?;
+
+    //    The destination path may use variables too, and the file built-ins describe the rendered path
+    let dest_path = &scaffold::render_destination(
+        &args.template_name,
+        &args.destination_file_path,
+        variables,
+        &mut vars,
+        &loader,
+        output,
+    )?;
+    context::update_file_variables(&mut vars, dest_path, &args.set);
+    output.verbose(format!("\t\t[VERBOSE] Rendering with {} variable(s).", vars.len()));
+    let mut template_content =
+        prompt::render_resolved(&args.template_name, template_body, variables, &mut vars, &loader, output)?;
+    if args.managed {
+        let comment = managed::comment_for(dest_path, template_entry.language.as_deref());
+        template_content = managed::wrap(&template_content, &args.template_name, comment);
+    }
+
+    // 4. Handle destination file
+    let planned = plan_write(dest_path, template_content.as_bytes(), args, default_strategy.clone(), force, output)?;
+    let strategy = args.write_strategy().or_else(|| default_strategy.clone());
+    commit(vec![planned], strategy, args, force, store, output)?;
+
+    report_applied(args, dest_path, output);
+    Ok(())
+}
+
+/// Recreates the files of a directory template under the destination directory.
+///
+/// Every file is rendered before any is written, so a missing variable or a syntax
+/// error leaves the destination untouched. Files that are not UTF-8 text are copied
+/// as they are.
+fn apply_directory(
+    args: &ApplyArgs,
+    force: bool,
+    store: &Store,
+    template_entry: &TemplateEntry,
+    stored_dir: &Path,
+    loader: &StoreLoader,
+    output: &OutputConfig,
+) -> Result<(), AppError> {
+    let specs = &template_entry.variables;
+    let mut vars =
+        context::template_variables(&args.template_name, template_entry, &args.destination_file_path, &args.set);
+    prompt::resolve_declared(&args.template_name, specs, &mut vars, output)?;
+
+    let dest_dir = &scaffold::render_destination(
+        &args.template_name,
+        &args.destination_file_path,
+        specs,
+        &mut vars,
+        loader,
+        output,
+    )?;
+    if dest_dir.exists() && !dest_dir.is_dir() {
+        return Err(AppError::DestinationIsNotADirectory {
+            name: args.template_name.clone(),
+            dest: dest_dir.to_path_buf(),
+        });
+    }
+
+    let mut rendered = Vec::with_capacity(template_entry.files.len());
+    for file in &template_entry.files {
+        // Stored paths may use variables; a path that renders empty leaves the file out
+        let Some(dest_file) =
+            scaffold::render_relative_path(&args.template_name, file, specs, &mut vars, loader, output)?
+        else {
+            output.verbose(format!("\t\t[VERBOSE] Skipping '{}': its path renders empty.", file));
+            continue;
+        };
+        let stored_path = scaffold::join(stored_dir, file);
+        let dest_path = scaffold::join(dest_dir, &dest_file);
+        let bytes = fs::read(&stored_path).map_err(|io_err| match io_err.kind() {
+            std::io::ErrorKind::NotFound => AppError::TemplateFileMissing {
+                name: args.template_name.clone(),
+                path: stored_path.clone(),
+            },
+            _ => AppError::Io(io_err),
+        })?;
+
+        let content = match String::from_utf8(bytes) {
+            Ok(source) => {
+                context::update_file_variables(&mut vars, &dest_path, &args.set);
+                let name = format!("{}/{}", args.template_name, file);
+                let content = prompt::render_resolved(&name, &source, specs, &mut vars, loader, output)?;
+                if args.managed {
+                    let comment = managed::comment_for(&dest_path, template_entry.language.as_deref());
+                    managed::wrap(&content, &args.template_name, comment).into_bytes()
+                } else {
+                    content.into_bytes()
+                }
+            }
+            Err(not_text) => not_text.into_bytes(),
+        };
+        rendered.push((dest_file, dest_path, content));
+    }
+    output.verbose(format!("\t\t[VERBOSE] Rendered {} file(s).", rendered.len()));
+
+    // Plan every write before making any, so an existing file without a strategy stops the apply early
+    let mut planned = Vec::with_capacity(rendered.len());
+    for (file, dest_path, content) in rendered {
+        output.info(format!("\t\t{} {}", ">".magenta(), file.cyan()));
+        planned.push(plan_write(&dest_path, &content, args, template_entry.default_strategy.clone(), force, output)?);
+    }
+    let strategy = args.write_strategy().or_else(|| template_entry.default_strategy.clone());
+    commit(planned, strategy, args, force, store, output)?;
+
+    report_applied(args, dest_dir, output);
+    Ok(())
+}
+
+/// Reports a finished apply, or that nothing was written in a dry run.
+fn report_applied(args: &ApplyArgs, dest_path: &Path, output: &OutputConfig) {
+    if args.dry_run {
+        output.info(format!("\n\t{} Dry run: nothing was written.", "✓".green().bold()));
+        return;
+    }
+    output.success(
+        format!("\n\t{} Template '{}' applied to {}",
+        "✓ Successfully".green().bold(),
+        args.template_name.yellow(),
+        format!("{:?}", dest_path).cyan()
+    ));
+}
+
+/// The content a destination file will have after the apply.
+struct PlannedWrite {
+    path: PathBuf,
+    /// What is done to the file, recorded in the journal.
+    action: &'static str,
+    /// Current content, `None` when the file does not exist yet.
+    old: Option<Vec<u8>>,
+    new: Vec<u8>,
+}
 
-    let dest_path = &args.destination_file_path;
+/// Works out the content of `dest_path` after writing the rendered `content`, using the
+/// write strategy when the file exists. Nothing is written yet.
+fn plan_write(
+    dest_path: &Path,
+    content: &[u8],
+    args: &ApplyArgs,
+    default_strategy: Option<WriteStrategy>,
+    force: bool,
+    output: &OutputConfig,
+) -> Result<PlannedWrite, AppError> {
+    // Flags first, then the template's default
+    let strategy = match (args.write_strategy(), default_strategy) {
+        (Some(strategy), _) => Some(strategy),
+        (None, Some(strategy)) => {
+            output.verbose(format!("\t\t[VERBOSE] Using the template's default strategy: {strategy}."));
+            Some(strategy)
+        }
+        (None, None) => None,
+    };
 
-    // 3. Handle destination file
-    if dest_path.exists() {
-        if dest_path.is_dir() {
-            return Err(AppError::DestinationIsDirectory {
-                action: "apply template".to_string(),
+    if !dest_path.exists() {
+        // An anchor needs a file to be found in
+        if let Some(anchor) = strategy.as_ref().and_then(anchor_of) {
+            return Err(AppError::AnchorDestinationMissing {
                 dest: dest_path.to_path_buf(),
+                anchor: anchor.describe(),
             });
         }
+        // Destination file does not exist, create it
+        output.info(format!("\t\t{} Creating new file.", ">".magenta()));
+        return Ok(PlannedWrite {
+            path: dest_path.to_path_buf(),
+            action: "create",
+            old: None,
+            new: content.to_vec(),
+        });
+    }
+    if dest_path.is_dir() {
+        return Err(AppError::DestinationIsDirectory {
+            action: "apply template".to_string(),
+            dest: dest_path.to_path_buf(),
+        });
+    }
+    let original_content = fs::read(dest_path)?;
 
-        // Destination file exists, apply strategy
-        if args.overwrite {
+    // A managed block from an earlier apply is replaced in place, whatever the strategy
+    if args.managed
+        && let Some((mut existing, range)) = find_managed(dest_path, &args.template_name)?
+    {
+        output.info(format!("\t\t{} Replacing the template's managed block.", ">".magenta()));
+        existing.replace_range(range, &String::from_utf8_lossy(content));
+        return Ok(PlannedWrite {
+            path: dest_path.to_path_buf(),
+            action: "replace-block",
+            old: Some(original_content),
+            new: existing.into_bytes(),
+        });
+    }
+
+    // Destination file exists, apply the strategy
+    let (action, new_content) = match strategy {
+        Some(WriteStrategy::Overwrite) => {
             output.info(format!("\t\t{} Overwriting existing file.", ">".magenta()));
-            fs::write(dest_path, &template_content).map_err(|e| AppError::Io(e))?;
-        } else if args.append {
+            ("overwrite", content.to_vec())
+        }
+        Some(WriteStrategy::Append) => {
             output.info(format!("\t\t{} Appending to existing file.", ">".magenta()));
-            let mut file = OpenOptions::new().append(true).open(dest_path)?; // AppError::Io handles error
-            file.write_all(template_content.as_bytes())?;
-        } else if args.prepend {
+            ("append", [&original_content, content].concat())
+        }
+        Some(WriteStrategy::Prepend) => {
             output.info(format!("\t\t{} Prepending to existing file.", ">".magenta()));
-            let mut original_content = String::new();
-            File::open(dest_path)?.read_to_string(&mut original_content)?;
-
-            let new_content = format!("{}\n{}", template_content, original_content);
-            fs::write(dest_path, new_content)?;
-        } else if force {
-            // No specific strategy flag, but --force is active
+            ("prepend", [content, b"\n", &original_content].concat())
+        }
+        Some(WriteStrategy::After(pattern)) => {
+            ("insert", insert_at(dest_path, &original_content, content, &Anchor::After(&pattern), output)?)
+        }
+        Some(WriteStrategy::Before(pattern)) => {
+            ("insert", insert_at(dest_path, &original_content, content, &Anchor::Before(&pattern), output)?)
+        }
+        Some(WriteStrategy::AtMarker(name)) => {
+            ("insert", insert_at(dest_path, &original_content, content, &Anchor::Marker(&name), output)?)
+        }
+        None if force => {
+            // No strategy given, but --force is active
             output.info(
                 format!("\t\t{} Overwriting existing file (due to --force).",
                 ">".magenta()
             ));
-            fs::write(dest_path, &template_content)?;
-        } else {
-            // No strategy flag, no --force, and file exists
+            ("overwrite", content.to_vec())
+        }
+        None => {
+            // No strategy, no --force, and file exists
             return Err(AppError::DestinationFileExists(dest_path.to_path_buf()));
         }
+    };
+
+    Ok(PlannedWrite {
+        path: dest_path.to_path_buf(),
+        action,
+        old: Some(original_content),
+        new: new_content,
+    })
+}
+
+/// Shows the planned changes with `--diff`, and writes them unless `--dry-run` is given.
+///
+/// The apply is recorded in the journal before the first write, with the content every
+/// file has beforehand, so `tempo undo` can revert it even if it is cut short. Once the
+/// writes are done the entry is completed; a write that fails leaves only the files
+/// written before it recorded.
+fn commit(
+    planned: Vec<PlannedWrite>,
+    strategy: Option<WriteStrategy>,
+    args: &ApplyArgs,
+    force: bool,
+    store: &Store,
+    output: &OutputConfig,
+) -> Result<(), AppError> {
+    if args.diff {
+        for planned in &planned {
+            let label = planned.path.display().to_string();
+            // New files are diffed against nothing, like `diff -N`
+            let old_label = if planned.old.is_some() { label.clone() } else { "/dev/null".to_string() };
+            let old = planned.old.as_deref().unwrap_or_default();
+            if let Some(diff) = diff::unified(old, &planned.new, &old_label, &format!("{label} (after apply)")) {
+                output.data_no_nl(&diff);
+            }
+        }
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+
+    let _lock = lock::acquire(store, output)?;
+    let mut journal = Journal::load(store)?;
+    let changes: Vec<Change<'_>> = planned
+        .iter()
+        .map(|planned| Change {
+            path: &planned.path,
+            action: planned.action,
+            before: planned.old.as_deref(),
+            after: &planned.new,
+        })
+        .collect();
+    let id = journal.begin(&args.template_name, strategy.map(|s| s.to_string()), &changes)?;
+    output.verbose(format!("\t\t[VERBOSE] Backed up {} file(s) in the journal.", changes.len()));
+
+    let mut written = 0;
+    let mut result = Ok(());
+    for planned in &planned {
+        if let Err(e) = write(planned, force, output) {
+            result = Err(e);
+            break;
+        }
+        written += 1;
+    }
+    // A write error is the one worth reporting; the entry is still pending if completing fails
+    let completed = journal.complete(&id, written);
+    result.and(completed)
+}
+
+/// Writes one planned file, creating its parent directory if needed. Only `force`
+/// replaces a read-only file.
+fn write(planned: &PlannedWrite, force: bool, output: &OutputConfig) -> Result<(), AppError> {
+    if let Some(parent_dir) = planned.path.parent()
+        && !parent_dir.as_os_str().is_empty()
+        && !parent_dir.exists()
+    {
+        fs::create_dir_all(parent_dir)?; // AppError::Io handles error
+        output.info(
+            format!("\t\t{} Created parent directory: {:?}",
+            ">".magenta(),
+            parent_dir
+        ));
+    }
+    if force {
+        atomic::write_forced(&planned.path, &planned.new)?;
     } else {
-        // Destination file does not exist, create it
-        output.info(format!("\t\t{} Creating new file.", ">".magenta()));
-        if let Some(parent_dir) = dest_path.parent() {
-            if !parent_dir.exists() {
-                fs::create_dir_all(parent_dir)?; // AppError::Io handles error
-                output.info(
-                    format!("\t\t{} Created parent directory: {:?}",
-                    ">".magenta(),
-                    parent_dir
-                ));
+        atomic::write(&planned.path, &planned.new)?;
+    }
+    Ok(())
+}
+
+/// The anchor of an inserting strategy, to report it when there is no file to insert into.
+fn anchor_of(strategy: &WriteStrategy) -> Option<Anchor<'_>> {
+    match strategy {
+        WriteStrategy::After(pattern) => Some(Anchor::After(pattern)),
+        WriteStrategy::Before(pattern) => Some(Anchor::Before(pattern)),
+        WriteStrategy::AtMarker(name) => Some(Anchor::Marker(name)),
+        WriteStrategy::Overwrite | WriteStrategy::Append | WriteStrategy::Prepend => None,
+    }
+}
+
+/// Inserts rendered content into the existing file content at an anchor line.
+fn insert_at(
+    dest_path: &Path,
+    original_content: &[u8],
+    content: &[u8],
+    anchor: &Anchor<'_>,
+    output: &OutputConfig,
+) -> Result<Vec<u8>, AppError> {
+    output.info(format!("\t\t{} Inserting at {}.", ">".magenta(), anchor.describe()));
+    let existing = String::from_utf8_lossy(original_content);
+    let content = String::from_utf8_lossy(content);
+
+    let new_content = insert::insert(&existing, &content, anchor).map_err(|e| match e {
+        InsertError::InvalidPattern { pattern, reason } => AppError::InvalidAnchorPattern { pattern, reason },
+        InsertError::NotFound => AppError::AnchorNotFound {
+            dest: dest_path.to_path_buf(),
+            anchor: anchor.describe(),
+        },
+        InsertError::Ambiguous(lines) => AppError::AnchorAmbiguous {
+            dest: dest_path.to_path_buf(),
+            anchor: anchor.describe(),
+            lines,
+        },
+    })?;
+    Ok(new_content.into_bytes())
+}
+
+/// Finds the managed block `template_name` left in `dest_path`, returning the file's
+/// content and the block's byte range. Missing and non-text files have no block.
+fn find_managed(dest_path: &Path, template_name: &str) -> Result<Option<(String, Range<usize>)>, AppError> {
+    let existing = match fs::read_to_string(dest_path) {
+        Ok(existing) => existing,
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::NotFound | std::io::ErrorKind::InvalidData) => {
+            return Ok(None);
+        }
+        Err(e) => return Err(AppError::Io(e)),
+    };
+
+    let range = managed::find_block(&existing, template_name).map_err(|e| AppError::ManagedBlockInvalid {
+        name: template_name.to_string(),
+        dest: dest_path.to_path_buf(),
+        reason: match e {
+            BlockError::Unterminated(line) => format!("has no end marker (it begins on line {line})"),
+            BlockError::Duplicated(lines) => {
+                format!("appears more than once (lines {})", error::join_numbers(&lines))
+            }
+        },
+    })?;
+    Ok(range.map(|range| (existing, range)))
+}
+
+/// Handles `tempo apply --remove`: strips the template's managed blocks from the
+/// destination, found as the apply found it: the destination path is rendered, and for a
+/// directory template so is the path of each of its files.
+fn remove_managed(args: &ApplyArgs, force: bool, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    output.info(format!(
+        "\n\t{} managed block of {} from {}...",
+        "→ Removing".blue().bold(),
+        args.template_name.yellow().bold(),
+        format!("{:?}", args.destination_file_path).cyan()
+    ));
+
+    let layers = stores.load()?;
+    let (layer, template_entry) = config::find_template(&layers, &args.template_name)
+        .ok_or_else(|| AppError::TemplateNotFound(args.template_name.clone()))?;
+    let loader = StoreLoader::new(&layers);
+    let specs = if template_entry.is_directory() {
+        template_entry.variables.clone()
+    } else {
+        declared_variables(template_entry, &template_entry.stored_path(&layer.templates_dir))?
+    };
+
+    let mut vars =
+        context::template_variables(&args.template_name, template_entry, &args.destination_file_path, &args.set);
+    prompt::resolve_declared(&args.template_name, &specs, &mut vars, output)?;
+    let dest_path = &scaffold::render_destination(
+        &args.template_name,
+        &args.destination_file_path,
+        &specs,
+        &mut vars,
+        &loader,
+        output,
+    )?;
+
+    let mut dest_files = Vec::new();
+    if template_entry.is_directory() {
+        if dest_path.exists() && !dest_path.is_dir() {
+            return Err(AppError::DestinationIsNotADirectory {
+                name: args.template_name.clone(),
+                dest: dest_path.to_path_buf(),
+            });
+        }
+        for file in &template_entry.files {
+            if let Some(dest_file) =
+                scaffold::render_relative_path(&args.template_name, file, &specs, &mut vars, &loader, output)?
+            {
+                let dest_file = scaffold::join(dest_path, &dest_file);
+                context::update_file_variables(&mut vars, &dest_file, &args.set);
+                dest_files.push(dest_file);
             }
         }
-        fs::write(dest_path, &template_content)?;
+    } else if dest_path.is_dir() {
+        return Err(AppError::ManagedBlockInDirectory {
+            name: args.template_name.clone(),
+            dest: dest_path.to_path_buf(),
+        });
+    } else {
+        dest_files.push(dest_path.to_path_buf());
     }
 
-    output.success(
-        format!("\n\t{} Template '{}' applied to {}",
+    let mut planned = Vec::new();
+    for dest_file in dest_files {
+        let Some((existing, range)) = find_managed(&dest_file, &args.template_name)? else {
+            continue;
+        };
+        output.info(format!("\t\t{} {}", ">".magenta(), format!("{:?}", dest_file).cyan()));
+        let mut new_content = existing.clone();
+        new_content.replace_range(range, "");
+        planned.push(PlannedWrite {
+            path: dest_file,
+            action: "remove-block",
+            old: Some(existing.into_bytes()),
+            new: new_content.into_bytes(),
+        });
+    }
+    if planned.is_empty() {
+        return Err(AppError::ManagedBlockNotFound {
+            name: args.template_name.clone(),
+            dest: dest_path.to_path_buf(),
+        });
+    }
+    let count = planned.len();
+    commit(planned, None, args, force, &stores.global, output)?;
+
+    if args.dry_run {
+        output.info(format!("\n\t{} Dry run: nothing was written.", "✓".green().bold()));
+        return Ok(());
+    }
+    output.success(format!(
+        "\n\t{} Managed block of '{}' removed from {} file(s) in {}",
         "✓ Successfully".green().bold(),
         args.template_name.yellow(),
+        count,
         format!("{:?}", dest_path).cyan()
     ));
-
     Ok(())
 }
+
+/// The variables a single-file template declares: those of its front matter, which win
+/// over the copy in the manifest.
+fn declared_variables(template_entry: &TemplateEntry, template_file_path: &Path) -> Result<Vec<VariableSpec>, AppError> {
+    let template_source = fs::read_to_string(template_file_path)?;
+    let (front_matter, _) = frontmatter::split(&template_source).map_err(|reason| AppError::InvalidFrontMatter {
+        path: template_file_path.to_path_buf(),
+        reason,
+    })?;
+    Ok(front_matter.map_or_else(|| template_entry.variables.clone(), |fm| fm.variables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Commands};
+    use crate::manifest::Manifest;
+    use clap::Parser;
+
+    fn apply_args(args: &[&str]) -> ApplyArgs {
+        match Cli::parse_from([&["tempo", "apply"], args].concat()).command {
+            Commands::Apply(args) => args,
+            other => panic!("unexpected command: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_anchor_strategies_need_an_existing_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("new.rs");
+        let output = OutputConfig::new(false, true);
+
+        let args = apply_args(&["h", "new.rs", "--after", "fn main"]);
+        let err = plan_write(&dest, b"fn h() {}\n", &args, None, false, &output).err().unwrap();
+        assert!(matches!(err, AppError::AnchorDestinationMissing { anchor, .. } if anchor == "--after 'fn main'"));
+
+        // The template's default strategy counts too; other strategies create the file
+        let args = apply_args(&["h", "new.rs"]);
+        let marker = Some(WriteStrategy::AtMarker("helpers".to_string()));
+        assert!(plan_write(&dest, b"", &args, marker, false, &output).is_err());
+        let planned = plan_write(&dest, b"fn h() {}\n", &args, Some(WriteStrategy::Append), false, &output).unwrap();
+        assert_eq!((planned.action, planned.old), ("create", None));
+    }
+
+    #[test]
+    fn test_remove_strips_the_blocks_of_a_directory_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path().join("store"));
+        let templates_dir = store.templates_dir().unwrap();
+        fs::create_dir_all(templates_dir.join("kit").join("src")).unwrap();
+        fs::write(templates_dir.join("kit").join("src").join("main.rs"), "fn main() {}\n").unwrap();
+        fs::write(templates_dir.join("kit").join("build.rs"), "fn build() {}\n").unwrap();
+        fs::write(templates_dir.join("one.rs"), "fn one() {}\n").unwrap();
+        let mut manifest = Manifest::new();
+        let mut kit = TemplateEntry::new("kit".to_string(), String::new());
+        kit.files = vec!["build.rs".to_string(), "src/main.rs".to_string()];
+        manifest.add_template("kit".to_string(), kit);
+        manifest.add_template("one".to_string(), TemplateEntry::new("one.rs".to_string(), "rs".to_string()));
+        store.save_manifest(&manifest).unwrap();
+        let stores = Stores { local: None, global: store };
+        let output = OutputConfig::new(false, true);
+
+        let out = dir.path().join("out");
+        fs::create_dir_all(out.join("src")).unwrap();
+        fs::write(out.join("src").join("main.rs"), "// mine\n").unwrap();
+        let dest = out.to_str().unwrap();
+        run(&apply_args(&["kit", dest, "--managed", "--append"]), false, &stores, &output).unwrap();
+
+        // The destination is rendered as the apply renders it
+        let templated = format!("{}/{{{{ \"out\" }}}}", dir.path().display());
+        run(&apply_args(&["kit", &templated, "--remove"]), false, &stores, &output).unwrap();
+        assert_eq!(fs::read_to_string(out.join("src").join("main.rs")).unwrap(), "// mine\n");
+        assert_eq!(fs::read_to_string(out.join("build.rs")).unwrap(), "");
+
+        let err = run(&apply_args(&["one", dest, "--remove"]), false, &stores, &output).unwrap_err();
+        assert!(matches!(err, AppError::ManagedBlockInDirectory { .. }));
+        let err = run(&apply_args(&["missing", dest, "--remove"]), false, &stores, &output).unwrap_err();
+        assert!(matches!(err, AppError::TemplateNotFound(_)));
+    }
+}
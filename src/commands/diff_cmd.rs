@@ -0,0 +1,50 @@
+use crate::cli::DiffArgs;
+use crate::config::{self, Stores};
+use crate::diff;
+use crate::error::AppError;
+use crate::history;
+use crate::output::OutputConfig;
+use colored::*;
+use std::fs;
+
+/// Handles the `tempo diff` command.
+///
+/// Prints a unified diff from an earlier revision of a template to its current content.
+pub fn run(args: &DiffArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let layers = stores.load()?;
+    let (layer, template_entry) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
+        None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
+    };
+    if template_entry.is_directory() {
+        return Err(AppError::DirectoryTemplateUnsupported {
+            action: "diff".to_string(),
+            name: args.template_name.clone(),
+        });
+    }
+
+    let revision = history::revision(template_entry, &args.template_name, args.rev)?;
+    let old = history::read_object(&layer.store, &args.template_name, revision)?;
+    let template_file_path = template_entry.stored_path(&layer.templates_dir);
+    let new = fs::read(&template_file_path).map_err(|_| AppError::TemplateFileMissing {
+        name: args.template_name.clone(),
+        path: template_file_path.clone(),
+    })?;
+    output.verbose(format!(
+        "\t\t[VERBOSE] Comparing object {} with {:?}.",
+        revision.object, template_file_path
+    ));
+
+    let old_label = format!("{} (revision {})", args.template_name, args.rev);
+    let new_label = format!("{} (current)", args.template_name);
+    match diff::unified(&old, &new, &old_label, &new_label) {
+        Some(diff) => output.data_no_nl(diff),
+        None => output.info(format!(
+            "\t{} Revision {} of '{}' is the same as the current content.",
+            "=".dimmed(),
+            args.rev,
+            args.template_name
+        )),
+    }
+    Ok(())
+}
@@ -0,0 +1,315 @@
+use crate::cli::DoctorArgs;
+use crate::commands::reindex;
+use crate::config::{Layer, Stores};
+use crate::error::AppError;
+use crate::frontmatter;
+use crate::lock;
+use crate::manifest::Manifest;
+use crate::namespace;
+use crate::output::OutputConfig;
+use crate::scaffold;
+use crate::template;
+use colored::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A mismatch between a store's manifest and its `templates/` directory.
+#[derive(Debug, PartialEq)]
+enum Problem {
+    /// An entry whose stored file or directory is gone.
+    MissingFile { name: String, path: PathBuf },
+    /// Files of a directory template that are gone, while the directory is still there.
+    MissingDirectoryFiles { name: String, files: Vec<String> },
+    /// A file under `templates/` that no entry refers to, relative with `/` separators.
+    OrphanFile { relative_path: String },
+    /// The extension recorded in the manifest differs from the stored file's.
+    ExtensionMismatch { name: String, recorded: String, actual: String },
+    /// The front matter or the template syntax does not parse.
+    Unparsable { name: String, path: PathBuf, reason: String },
+}
+
+impl Problem {
+    fn describe(&self) -> String {
+        match self {
+            Problem::MissingFile { name, path } => {
+                format!("Template '{name}' has no stored file: {path:?} does not exist.")
+            }
+            Problem::MissingDirectoryFiles { name, files } => {
+                format!("Template '{name}' is missing {} stored file(s): {}.", files.len(), files.join(", "))
+            }
+            Problem::OrphanFile { relative_path } => {
+                format!("File '{relative_path}' is not part of any template.")
+            }
+            Problem::ExtensionMismatch { name, recorded, actual } => {
+                format!("Template '{name}' is recorded with extension '{recorded}', but its file has '{actual}'.")
+            }
+            Problem::Unparsable { name, path, reason } => {
+                format!("Template '{name}' does not parse ({path:?}): {reason}")
+            }
+        }
+    }
+
+    /// Whether `--fix` can repair the problem; templates that do not parse need a human.
+    fn is_fixable(&self) -> bool {
+        !matches!(self, Problem::Unparsable { .. })
+    }
+}
+
+/// Handles the `tempo doctor` command.
+///
+/// Checks every store for entries without files, files without entries, extension
+/// mismatches and templates that do not parse. With `--fix`, dangling entries are
+/// pruned and orphan files adopted into the manifest.
+pub fn run(args: &DoctorArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    output.info(format!(
+        "\n\t{} the template store{}...",
+        "→ Checking".blue().bold(),
+        if args.fix { " and repairing it" } else { "" }
+    ));
+
+    let (mut remaining, mut fixable) = (0, 0);
+    for (layer, store) in stores.layers() {
+        // Held for the whole check with --fix, so the repairs are made against what was scanned
+        let _lock = if args.fix { Some(lock::acquire(store, output)?) } else { None };
+        let mut manifest = store.load_manifest()?;
+        let templates_dir = store.templates_dir()?;
+        let problems = scan(&manifest, &templates_dir)?;
+        if layer == Layer::Local || stores.local.is_some() {
+            output.info(format!("\t\t{} {} store ({:?}):", ">".magenta(), layer, store.root_path()));
+        }
+        output.verbose(format!(
+            "\t\t[VERBOSE] Checked {} template(s) in the {} store; found {} problem(s).",
+            manifest.templates.len(),
+            layer,
+            problems.len()
+        ));
+
+        let mut changed = false;
+        for problem in &problems {
+            if !args.fix || !problem.is_fixable() {
+                output.data(format!("\t\t{} {}", "✖".red(), problem.describe()));
+                remaining += 1;
+                fixable += usize::from(problem.is_fixable());
+                continue;
+            }
+            match fix(problem, &mut manifest, &templates_dir) {
+                Ok(action) => {
+                    output.data(format!("\t\t{} {} {}", "✓".green(), problem.describe(), action.dimmed()));
+                    changed = true;
+                }
+                Err(reason) => {
+                    output.data(format!("\t\t{} {} {}", "✖".red(), problem.describe(), reason.dimmed()));
+                    remaining += 1;
+                }
+            }
+        }
+        if changed {
+            store.save_manifest(&manifest)?;
+            output.verbose(format!("\t\t[VERBOSE] Manifest of the {} store saved.", layer));
+        }
+    }
+
+    if remaining > 0 {
+        let hint = if !args.fix && fixable > 0 {
+            format!(" Run 'tempo doctor --fix' to repair {fixable} of them.")
+        } else {
+            String::new()
+        };
+        return Err(AppError::DoctorFoundProblems { count: remaining, hint });
+    }
+    output.success(format!("\n\t{} The template store is consistent.", "✓".green().bold()));
+    Ok(())
+}
+
+/// Compares a manifest with the files under `templates_dir`.
+fn scan(manifest: &Manifest, templates_dir: &Path) -> Result<Vec<Problem>, AppError> {
+    let mut problems = Vec::new();
+    let mut names: Vec<&String> = manifest.templates.keys().collect();
+    names.sort();
+
+    for name in names {
+        let entry = &manifest.templates[name];
+        let path = entry.stored_path(templates_dir);
+        if !path.exists() {
+            problems.push(Problem::MissingFile { name: name.clone(), path });
+            continue;
+        }
+
+        if entry.is_directory() {
+            let missing: Vec<String> = entry
+                .files
+                .iter()
+                .filter(|file| !scaffold::join(&path, file).is_file())
+                .cloned()
+                .collect();
+            for file in entry.files.iter().filter(|file| !missing.contains(file)) {
+                let file_path = scaffold::join(&path, file);
+                if let Some(reason) = parse_error(&file_path, false)? {
+                    problems.push(Problem::Unparsable { name: name.clone(), path: file_path, reason });
+                }
+            }
+            if !missing.is_empty() {
+                problems.push(Problem::MissingDirectoryFiles { name: name.clone(), files: missing });
+            }
+            continue;
+        }
+
+        let actual = extension_of(name, &entry.filename_in_storage);
+        if !actual.eq_ignore_ascii_case(&entry.source_extension) {
+            problems.push(Problem::ExtensionMismatch {
+                name: name.clone(),
+                recorded: entry.source_extension.clone(),
+                actual: actual.to_string(),
+            });
+        }
+        if let Some(reason) = parse_error(&path, true)? {
+            problems.push(Problem::Unparsable { name: name.clone(), path, reason });
+        }
+    }
+
+    // Hidden files (editor swap files, .DS_Store, ...) cannot be templates, so they are not reported
+    for relative_path in scaffold::collect_files(templates_dir)? {
+        if relative_path.split('/').any(|part| part.starts_with('.')) {
+            continue;
+        }
+        let owned = manifest.templates.values().any(|entry| {
+            entry.filename_in_storage == relative_path
+                || (entry.is_directory() && namespace::storage_overlaps(&entry.filename_in_storage, &relative_path))
+        });
+        if !owned {
+            problems.push(Problem::OrphanFile { relative_path });
+        }
+    }
+    Ok(problems)
+}
+
+/// Why a stored file does not parse as a template, if it doesn't. Files that are not
+/// UTF-8 text are copied as they are, so they always pass.
+fn parse_error(path: &Path, with_front_matter: bool) -> Result<Option<String>, AppError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => return Ok(None),
+        Err(e) => return Err(AppError::Io(e)),
+    };
+    let body = if with_front_matter {
+        match frontmatter::split(&content) {
+            Ok((_, body)) => body,
+            Err(reason) => return Ok(Some(format!("invalid front matter: {reason}"))),
+        }
+    } else {
+        &content
+    };
+    Ok(template::parse(body).err().map(|e| e.to_string()))
+}
+
+/// Repairs one problem in `manifest`, returning what was done or why it could not be.
+fn fix(problem: &Problem, manifest: &mut Manifest, templates_dir: &Path) -> Result<String, String> {
+    match problem {
+        Problem::MissingFile { name, .. } => {
+            manifest.remove_template(name);
+            Ok("(entry removed)".to_string())
+        }
+        Problem::MissingDirectoryFiles { name, files } => {
+            let entry = manifest.get_template_mut(name).ok_or("(entry is gone)")?;
+            entry.files.retain(|file| !files.contains(file));
+            if entry.files.is_empty() {
+                manifest.remove_template(name);
+                return Ok("(no files left, entry removed)".to_string());
+            }
+            Ok("(files dropped from the entry)".to_string())
+        }
+        Problem::ExtensionMismatch { name, actual, .. } => {
+            let entry = manifest.get_template_mut(name).ok_or("(entry is gone)")?;
+            entry.source_extension = actual.to_lowercase();
+            Ok("(extension updated)".to_string())
+        }
+        Problem::OrphanFile { relative_path } => adopt(relative_path, manifest, templates_dir),
+        Problem::Unparsable { .. } => Err("(fix the template by hand)".to_string()),
+    }
+}
+
+/// Adds an orphan file to the manifest, named the way `tempo reindex` would name it.
+fn adopt(relative_path: &str, manifest: &mut Manifest, templates_dir: &Path) -> Result<String, String> {
+    let (name, entry) = reindex::file_entry(templates_dir, relative_path);
+    namespace::parse(&name).map_err(|reason| format!("(cannot adopt it as '{name}': {reason})"))?;
+    if manifest.get_template(&name).is_some() {
+        return Err(format!("(cannot adopt it: template '{name}' already exists)"));
+    }
+    if let Some((other, _)) = manifest
+        .templates
+        .iter()
+        .find(|(_, entry)| namespace::storage_overlaps(&entry.filename_in_storage, relative_path))
+    {
+        return Err(format!("(cannot adopt it: it clashes with template '{other}')"));
+    }
+    manifest.add_template(name.clone(), entry);
+    Ok(format!("(adopted as '{name}')"))
+}
+
+/// The extension template `name` is stored with: `ext` for `<name>.<ext>` and `""` for a
+/// bare `<name>`, so a dotted name such as `v1.2` is not mistaken for an extension. A file
+/// not named after its template falls back to the extension of its file name.
+fn extension_of<'a>(name: &str, relative_path: &'a str) -> &'a str {
+    match relative_path.strip_prefix(name) {
+        Some("") => "",
+        Some(rest) if rest.starts_with('.') => &rest[1..],
+        _ => Path::new(relative_path).extension().and_then(|ext| ext.to_str()).unwrap_or(""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::TemplateEntry;
+
+    #[test]
+    fn test_scan_and_fix_repair_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates_dir = dir.path();
+        fs::create_dir(templates_dir.join("cp")).unwrap();
+        fs::write(templates_dir.join("io.rs"), "fn main() {}").unwrap();
+        fs::write(templates_dir.join("cp").join("segtree.cpp"), "+++\ndescription = \"Tree\"\n+++\nstruct T;").unwrap();
+        fs::write(templates_dir.join("broken.txt"), "{% if x %}").unwrap();
+        fs::write(templates_dir.join(".swp"), "").unwrap();
+
+        let mut manifest = Manifest::new();
+        manifest.add_template("io".to_string(), TemplateEntry::new("io.rs".to_string(), "py".to_string()));
+        manifest.add_template("gone".to_string(), TemplateEntry::new("gone.rs".to_string(), "rs".to_string()));
+        manifest.add_template("broken".to_string(), TemplateEntry::new("broken.txt".to_string(), "txt".to_string()));
+
+        let problems = scan(&manifest, templates_dir).unwrap();
+        assert!(matches!(&problems[0], Problem::Unparsable { name, .. } if name == "broken"));
+        assert!(matches!(&problems[1], Problem::MissingFile { name, .. } if name == "gone"));
+        assert_eq!(
+            problems[2..],
+            [
+                Problem::ExtensionMismatch { name: "io".into(), recorded: "py".into(), actual: "rs".into() },
+                Problem::OrphanFile { relative_path: "cp/segtree.cpp".into() },
+            ]
+        );
+
+        for problem in problems.iter().filter(|problem| problem.is_fixable()) {
+            fix(problem, &mut manifest, templates_dir).unwrap();
+        }
+        assert!(manifest.get_template("gone").is_none());
+        assert_eq!(manifest.get_template("io").unwrap().source_extension, "rs");
+        let adopted = manifest.get_template("cp/segtree").unwrap();
+        assert_eq!(adopted.description.as_deref(), Some("Tree"));
+        assert_eq!(scan(&manifest, templates_dir).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_dotted_name_without_extension_is_not_a_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("v1.2"), "all:").unwrap();
+        fs::write(dir.path().join("v2.0.mk"), "all:").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.add_template("v1.2".to_string(), TemplateEntry::new("v1.2".to_string(), String::new()));
+        manifest.add_template("v2.0".to_string(), TemplateEntry::new("v2.0.mk".to_string(), "mk".to_string()));
+
+        assert_eq!(scan(&manifest, dir.path()).unwrap(), []);
+        assert_eq!(extension_of("v1.2", "v1.2"), "");
+        assert_eq!(extension_of("v2.0", "v2.0.mk"), "mk");
+    }
+}
@@ -1,13 +1,19 @@
 use chrono::Utc;
 use crate::cli::EditArgs;
-use crate::config;
+use crate::config::{self, Stores};
 use crate::error::AppError;
+use crate::frontmatter;
+use crate::git;
+use crate::history;
+use crate::lock;
+use crate::template;
 use colored::*;
 use edit;
+use std::fs;
 use crate::output::OutputConfig;
 
 /// Handles the `tempo edit` command.
-pub fn run(args: &EditArgs, output: &OutputConfig) -> Result<(), AppError> {
+pub fn run(args: &EditArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
     output.info(
         format!("\n\t{} template {}...",
         "→ Opening".blue().bold(),
@@ -15,19 +21,24 @@ pub fn run(args: &EditArgs, output: &OutputConfig) -> Result<(), AppError> {
     ));
 
     // --- Load Manifest ---
-    let mut manifest = config::load_manifest()?; // mutable because we might update it
+    let layers = stores.load()?;
     output.verbose("VERBOSE] Manifest loaded for 'edit' command.");
 
-    // --- Find template entry in manifest ---
-    let template_entry = match manifest.get_template(&args.template_name) {
-        Some(entry) => entry,
+    // --- Find template entry in manifest, local store first ---
+    let (layer, template_entry) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
         None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
     };
+    let store = &layer.store;
+    if template_entry.is_directory() {
+        return Err(AppError::DirectoryTemplateUnsupported {
+            action: "edit".to_string(),
+            name: args.template_name.clone(),
+        });
+    }
 
-    // Get the actual filename and construct the path
-    let filename_in_storage = template_entry.filename_in_storage.clone();
-    let templates_dir = config::get_templates_dir()?;
-    let template_file_path = templates_dir.join(&filename_in_storage);
+    // Construct the path of the stored file
+    let template_file_path = template_entry.stored_path(&layer.templates_dir);
 
     output.info(
         format!("\t\t{} Editing file: {}",
@@ -43,6 +54,9 @@ pub fn run(args: &EditArgs, output: &OutputConfig) -> Result<(), AppError> {
         });
     }
 
+    // Kept as a revision if the edit changes the file
+    let before = fs::read(&template_file_path)?;
+
     // Use the `edit` crate to open the file 
     match edit::edit_file(&template_file_path) {
         Ok(()) => {
@@ -51,14 +65,51 @@ pub fn run(args: &EditArgs, output: &OutputConfig) -> Result<(), AppError> {
                 args.template_name
             ));
 
+            let changed = fs::read(&template_file_path).ok().as_deref() != Some(before.as_slice());
+
+            // Re-read the front matter and the parent template, since the edit may have changed them
+            let content = fs::read_to_string(&template_file_path).ok();
+            let front_matter = match content.as_deref().map(frontmatter::split) {
+                Some(Ok((front_matter, _))) => front_matter,
+                Some(Err(reason)) => {
+                    output.warn(format!(
+                        "\t\tWarning: Front matter of '{}' is invalid, keeping the previous metadata: {}",
+                        args.template_name, reason
+                    ));
+                    None
+                }
+                None => None,
+            };
+            let parent = content.as_deref().map(|content| {
+                let body = frontmatter::split(content).map_or(content, |(_, body)| body);
+                template::parent_of(body)
+            });
+
+            // Reload under the lock, so changes other commands made while the editor was open are kept
+            let _lock = lock::acquire(store, output)?;
+            let mut manifest = store.load_manifest()?;
+
             // Update `updated_at` timestamp in manifest 
             if let Some(entry_to_update) = manifest.get_template_mut(&args.template_name) {
+                if changed && let Some(revision) = history::keep(store, entry_to_update, &before)? {
+                    entry_to_update.history.push(revision);
+                    output.verbose(format!(
+                        "\t\t[VERBOSE] Previous content kept as revision {}.",
+                        entry_to_update.history.len()
+                    ));
+                }
                 entry_to_update.updated_at = Utc::now();
-                config::save_manifest(&manifest)?; // Save the updated manifest
+                if let Some(front_matter) = &front_matter {
+                    front_matter.apply_to(entry_to_update);
+                }
+                if let Some(parent) = parent {
+                    entry_to_update.extends = parent;
+                }
+                store.save_manifest(&manifest)?; // Save the updated manifest
+                git::autocommit(store.root_path(), &format!("tempo edit {}", args.template_name), output);
                 output.verbose(format!("[VERBOSE] Manifest saved with updated timestamp for '{}'.", args.template_name));
             } else {
-                // This case should be rare if get_template succeeded earlier,
-                // but handle defensively.
+                // The template was removed by another command while the editor was open
                 output.warn(format!(
                     "Warning: Could not find template '{}' in manifest to update timestamp after edit.",
                     args.template_name
@@ -0,0 +1,61 @@
+use crate::bundle;
+use crate::cli::ExportArgs;
+use crate::config::{self, Stores};
+use crate::error::AppError;
+use crate::manifest::TemplateEntry;
+use crate::output::OutputConfig;
+use colored::*;
+use std::path::Path;
+
+/// Handles the `tempo export` command.
+///
+/// Picks the templates named or tagged, or all of them, from every store, a project
+/// template hiding a global one of the same name as usual, and packs them into a bundle.
+pub fn run(args: &ExportArgs, force: bool, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    output.info(format!(
+        "\n\t{} templates to {}...",
+        "→ Exporting".blue().bold(),
+        format!("{:?}", args.output).cyan()
+    ));
+    if args.output.exists() && !force {
+        return Err(AppError::BundleExists(args.output.clone()));
+    }
+
+    let layers = stores.load()?;
+    let mut selected: Vec<(&str, &TemplateEntry, &Path)> = Vec::new();
+    for name in &args.names {
+        let (layer, entry) = config::find_template(&layers, name).ok_or_else(|| AppError::TemplateNotFound(name.clone()))?;
+        if !selected.iter().any(|(other, _, _)| other == name) {
+            selected.push((name, entry, &layer.templates_dir));
+        }
+    }
+    let everything = args.names.is_empty() && args.tags.is_empty();
+    // Layers come project store first, so a shadowed global template is left out
+    for layer in &layers {
+        let mut names: Vec<&String> = layer.manifest.templates.keys().collect();
+        names.sort();
+        for name in names {
+            let entry = &layer.manifest.templates[name];
+            let wanted = everything || entry.tags.iter().any(|tag| args.tags.contains(tag));
+            if wanted && !selected.iter().any(|(other, _, _)| *other == name.as_str()) {
+                selected.push((name, entry, &layer.templates_dir));
+            }
+        }
+    }
+    if selected.is_empty() {
+        return Err(AppError::NothingToExport);
+    }
+    for (name, _, _) in &selected {
+        output.verbose(format!("\t\t[VERBOSE] Packing '{}'.", name));
+    }
+
+    let files = bundle::write(&args.output, &selected)?;
+    output.success(format!(
+        "\t{} Exported {} template(s), {} file(s), to {}.",
+        "✓".green().bold(),
+        selected.len(),
+        files,
+        format!("{:?}", args.output).cyan()
+    ));
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use crate::cli::HistoryArgs;
+use crate::config::{self, Stores};
+use crate::error::AppError;
+use crate::output::OutputConfig;
+use chrono::Local;
+use colored::*;
+
+/// Handles the `tempo history` command.
+///
+/// Lists the earlier revisions of a template, oldest first, numbered for `tempo restore`
+/// and `tempo diff`.
+pub fn run(args: &HistoryArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let layers = stores.load()?;
+    let (_, template_entry) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
+        None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
+    };
+
+    output.info(format!(
+        "\t{} {}:",
+        "Revisions of".blue().bold().underline(),
+        args.template_name.cyan().bold()
+    ));
+    if template_entry.history.is_empty() {
+        output.info(format!("\t\t{}", "No earlier revisions are kept.".yellow()));
+    }
+    for (index, revision) in template_entry.history.iter().enumerate() {
+        let mut line = format!(
+            "\t\t{} {} {}",
+            format!("{:>3}.", index + 1).dimmed(),
+            revision.saved_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            revision.object[..12.min(revision.object.len())].magenta()
+        );
+        if revision.filename_in_storage != template_entry.filename_in_storage {
+            line.push_str(&format!(" {}", format!("({})", revision.filename_in_storage).dimmed()));
+        }
+        output.data(line);
+    }
+    output.data(format!(
+        "\t\t{} {} {}",
+        " now".dimmed(),
+        template_entry.updated_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        "current".green()
+    ));
+    Ok(())
+}
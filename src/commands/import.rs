@@ -0,0 +1,371 @@
+use crate::atomic;
+use crate::bundle::{self, Bundle};
+use crate::cli::{ConflictPolicy, ImportArgs};
+use crate::config::{Store, Stores};
+use crate::error::AppError;
+use crate::git;
+use crate::history;
+use crate::lock;
+use crate::manifest::{Manifest, Revision, TemplateEntry};
+use crate::namespace;
+use crate::output::OutputConfig;
+use crate::scaffold;
+use colored::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What became of a bundled template.
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Added,
+    /// Added under another name, as its own was taken.
+    Renamed(String),
+    /// Replaced the template of the same name.
+    Replaced,
+    /// Left out, for this reason.
+    Skipped(String),
+}
+
+/// Handles the `tempo import` command.
+///
+/// Adds the templates of a bundle with the metadata they had in the exporting store,
+/// their `created_at`/`updated_at` and tags included. Names already taken are handled
+/// per `--on-conflict`.
+pub fn run(args: &ImportArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    output.info(format!(
+        "\n\t{} templates from {}...",
+        "→ Importing".blue().bold(),
+        format!("{:?}", args.bundle).cyan()
+    ));
+    let bundle = bundle::read(&args.bundle)?;
+    validate(&bundle, &args.bundle)?;
+    output.verbose(format!("\t\t[VERBOSE] Bundle holds {} template(s).", bundle.manifest.templates.len()));
+
+    // > Pick the store: the project's with --local (creating `.tempo/` here if there is none), else the global one
+    let store = &stores.target(args.local, output)?;
+
+    let _lock = lock::acquire(store, output)?;
+    let mut manifest = store.load_manifest()?;
+    let templates_dir = store.templates_dir()?;
+    let outcomes = import(&bundle, args.on_conflict, store, &templates_dir, &mut manifest);
+
+    let mut imported = 0;
+    for (name, outcome) in &outcomes {
+        let line = match outcome {
+            Outcome::Added => format!("'{}' added", name.yellow()),
+            Outcome::Renamed(new_name) => format!("'{}' added as '{}'", name.yellow(), new_name.yellow().bold()),
+            Outcome::Replaced => format!("'{}' replaced", name.yellow()),
+            Outcome::Skipped(reason) => {
+                output.warn(format!("\t\tWarning: Skipped '{}': {}.", name, reason));
+                continue;
+            }
+        };
+        imported += 1;
+        output.info(format!("\t\t{} {}", ">".magenta(), line));
+    }
+
+    for (name, parent, new_parent) in stale_parents(&bundle, &outcomes) {
+        output.warn(format!(
+            "\t\tWarning: '{}' extends '{}', which was imported as '{}'; it still extends the existing '{}'.",
+            name, parent, new_parent, parent
+        ));
+    }
+
+    if imported > 0 {
+        store.save_manifest(&manifest)?;
+        output.verbose(format!("\t\t[VERBOSE] Manifest saved. Total templates: {}.", manifest.templates.len()));
+        let bundle_name = args.bundle.file_name().unwrap_or(args.bundle.as_os_str()).to_string_lossy();
+        git::autocommit(store.root_path(), &format!("tempo import {bundle_name}"), output);
+    }
+
+    output.success(format!(
+        "\t{} Imported {} of {} template(s).",
+        "✓".green().bold(),
+        imported,
+        outcomes.len()
+    ));
+    Ok(())
+}
+
+/// Checks the whole bundle before anything is written: every name is a valid template
+/// name and every file its manifest lists is in it.
+fn validate(bundle: &Bundle, path: &Path) -> Result<(), AppError> {
+    let invalid = |reason: String| AppError::InvalidBundle { path: path.to_path_buf(), reason };
+    for (name, entry) in &bundle.manifest.templates {
+        namespace::parse(&stored_filename(name, entry))
+            .map_err(|reason| invalid(format!("template '{name}' has an invalid name: {reason}")))?;
+        if entry.files.iter().any(|file| file.split('/').any(|part| part.is_empty() || part == "." || part == "..")) {
+            return Err(invalid(format!("template '{name}' lists a file outside of its directory")));
+        }
+        if let Some(file) = bundle::stored_files(entry).into_iter().find(|file| bundle.file(file).is_none()) {
+            return Err(invalid(format!("the file '{file}' of template '{name}' is missing")));
+        }
+    }
+    Ok(())
+}
+
+/// Stores the bundled templates under `templates_dir` and adds their entries to
+/// `manifest`, which the caller saves.
+fn import(
+    bundle: &Bundle,
+    policy: ConflictPolicy,
+    store: &Store,
+    templates_dir: &Path,
+    manifest: &mut Manifest,
+) -> Vec<(String, Outcome)> {
+    let mut names: Vec<&String> = bundle.manifest.templates.keys().collect();
+    names.sort();
+    let mut outcomes = Vec::new();
+
+    for name in names {
+        let bundled = &bundle.manifest.templates[name];
+        let (target, outcome) = match (manifest.get_template(name).is_some(), policy) {
+            (false, _) => (name.clone(), Outcome::Added),
+            (true, ConflictPolicy::Skip) => {
+                outcomes.push((name.clone(), Outcome::Skipped("a template of that name exists".to_string())));
+                continue;
+            }
+            (true, ConflictPolicy::Rename) => {
+                let new_name = free_name(manifest, name, bundled, templates_dir);
+                (new_name.clone(), Outcome::Renamed(new_name))
+            }
+            (true, ConflictPolicy::Overwrite) => (name.clone(), Outcome::Replaced),
+        };
+
+        // > A namespace cannot share its directory with a template stored under the same path
+        let filename = stored_filename(&target, bundled);
+        if let Some((other, _)) = manifest.templates.iter().find(|(other, entry)| {
+            **other != target && namespace::storage_overlaps(&entry.filename_in_storage, &filename)
+        }) {
+            let reason = format!("its storage path '{filename}' clashes with template '{other}'");
+            outcomes.push((name.clone(), Outcome::Skipped(reason)));
+            continue;
+        }
+
+        let dest_path = scaffold::join(templates_dir, &filename);
+        let existing = manifest.get_template(&target);
+        if existing.is_none() && dest_path.exists() {
+            let reason = format!("{dest_path:?} is already taken by a file missing from the manifest");
+            outcomes.push((name.clone(), Outcome::Skipped(reason)));
+            continue;
+        }
+        // A failure leaves this template as it was, so the manifest still matches the stored files
+        let history = match store_template(bundle, bundled, existing, store, templates_dir, &dest_path) {
+            Ok(history) => history,
+            Err(e) => {
+                outcomes.push((name.clone(), Outcome::Skipped(format!("its files could not be stored: {e}"))));
+                continue;
+            }
+        };
+
+        // Inserted as is rather than with `add_template`, which would reset the timestamps
+        let mut entry = bundled.clone();
+        entry.filename_in_storage = filename;
+        entry.history = history;
+        manifest.templates.insert(target, entry);
+        outcomes.push((name.clone(), outcome));
+    }
+    outcomes
+}
+
+/// Writes the files of `bundled` to `dest_path`, replacing the stored content of `existing`
+/// if there is one. Returns the history of the new entry: that of `existing`, plus the
+/// content it replaces.
+///
+/// A replacement is written next to its destination first, and the old content is only
+/// removed once the new one is complete.
+fn store_template(
+    bundle: &Bundle,
+    bundled: &TemplateEntry,
+    existing: Option<&TemplateEntry>,
+    store: &Store,
+    templates_dir: &Path,
+    dest_path: &Path,
+) -> Result<Vec<Revision>, AppError> {
+    let Some(existing) = existing else {
+        write_files(bundle, bundled, dest_path)?;
+        return Ok(Vec::new());
+    };
+
+    // Keep the content being replaced as a revision, so `tempo restore` can bring it back
+    let mut history = existing.history.clone();
+    if let Some(revision) = history::snapshot(store, existing, templates_dir)? {
+        history.push(revision);
+    }
+
+    let file_name = dest_path.file_name().unwrap_or_default().to_string_lossy();
+    let staging = dest_path.with_file_name(format!(".{file_name}.tempo-import"));
+    if let Err(e) = write_files(bundle, bundled, &staging) {
+        let _ = scaffold::remove_stored(&staging);
+        return Err(e);
+    }
+    let old_path = existing.stored_path(templates_dir);
+    // A file is replaced by the rename itself, a directory has to go first
+    if old_path.exists() && (old_path != dest_path || old_path.is_dir()) {
+        scaffold::remove_stored(&old_path).map_err(|e| AppError::FileRemove {
+            path: old_path,
+            source_error: e,
+        })?;
+    }
+    fs::rename(&staging, dest_path)?;
+    Ok(history)
+}
+
+/// Writes the bundled files of a template to `dest_path`, a file or a directory.
+fn write_files(bundle: &Bundle, bundled: &TemplateEntry, dest_path: &Path) -> Result<(), AppError> {
+    let files: Vec<(PathBuf, String)> = if bundled.is_directory() {
+        bundled
+            .files
+            .iter()
+            .map(|file| (scaffold::join(dest_path, file), format!("{}/{file}", bundled.filename_in_storage)))
+            .collect()
+    } else {
+        vec![(dest_path.to_path_buf(), bundled.filename_in_storage.clone())]
+    };
+    for (path, stored) in files {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write(&path, bundle.file(&stored).unwrap_or_default())?;
+    }
+    Ok(())
+}
+
+/// Imported templates extending a bundled template that was renamed: they still extend the
+/// template of the original name, which is not the one they came with. Given as
+/// `(template, parent, the parent's new name)`.
+fn stale_parents(bundle: &Bundle, outcomes: &[(String, Outcome)]) -> Vec<(String, String, String)> {
+    let renamed = |name: &str| {
+        outcomes.iter().find_map(|(original, outcome)| match outcome {
+            Outcome::Renamed(new_name) if original == name => Some(new_name.clone()),
+            _ => None,
+        })
+    };
+    outcomes
+        .iter()
+        .filter_map(|(name, outcome)| {
+            let imported_as = match outcome {
+                Outcome::Added | Outcome::Replaced => name.clone(),
+                Outcome::Renamed(new_name) => new_name.clone(),
+                Outcome::Skipped(_) => return None,
+            };
+            let parent = bundle.manifest.get_template(name)?.extends.clone()?;
+            renamed(&parent).map(|new_parent| (imported_as, parent, new_parent))
+        })
+        .collect()
+}
+
+/// `<name>.<extension>`, or the bare name for a directory or a file without extension.
+fn stored_filename(name: &str, entry: &TemplateEntry) -> String {
+    if entry.is_directory() || entry.source_extension.is_empty() {
+        name.to_string()
+    } else {
+        format!("{name}.{}", entry.source_extension)
+    }
+}
+
+/// The first of `<name>-2`, `<name>-3`, ... that neither the manifest nor a stored file uses.
+fn free_name(manifest: &Manifest, name: &str, entry: &TemplateEntry, templates_dir: &Path) -> String {
+    (2..)
+        .map(|n| format!("{name}-{n}"))
+        .find(|candidate| {
+            manifest.get_template(candidate).is_none()
+                && !scaffold::join(templates_dir, &stored_filename(candidate, entry)).exists()
+        })
+        .unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_applies_conflict_policies_and_keeps_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = Store::new(dir.path().join("source"));
+        let source_dir = source.templates_dir().unwrap();
+        fs::write(source_dir.join("io.rs"), "bundled").unwrap();
+        let mut io = TemplateEntry::new("io.rs".to_string(), "rs".to_string());
+        io.tags = vec!["rust".to_string()];
+        io.created_at = chrono::DateTime::from_timestamp(1_600_000_000, 0).unwrap();
+        io.updated_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let path = dir.path().join("bundle.tar.gz");
+        bundle::write(&path, &[("io", &io, source_dir.as_path())]).unwrap();
+        let bundle = bundle::read(&path).unwrap();
+        validate(&bundle, &path).unwrap();
+
+        let target = Store::new(dir.path().join("target"));
+        let templates_dir = target.templates_dir().unwrap();
+        fs::write(templates_dir.join("io.rs"), "local").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.add_template("io".to_string(), TemplateEntry::new("io.rs".to_string(), "rs".to_string()));
+
+        let skipped = import(&bundle, ConflictPolicy::Skip, &target, &templates_dir, &mut manifest);
+        assert!(matches!(skipped[0].1, Outcome::Skipped(_)));
+        assert_eq!(fs::read_to_string(templates_dir.join("io.rs")).unwrap(), "local");
+
+        let renamed = import(&bundle, ConflictPolicy::Rename, &target, &templates_dir, &mut manifest);
+        assert_eq!(renamed[0].1, Outcome::Renamed("io-2".to_string()));
+        let entry = manifest.get_template("io-2").unwrap();
+        assert_eq!((entry.created_at, entry.updated_at), (io.created_at, io.updated_at));
+        assert_eq!(entry.tags, io.tags);
+        assert_eq!(fs::read_to_string(templates_dir.join("io-2.rs")).unwrap(), "bundled");
+
+        let replaced = import(&bundle, ConflictPolicy::Overwrite, &target, &templates_dir, &mut manifest);
+        assert_eq!(replaced[0].1, Outcome::Replaced);
+        assert_eq!(fs::read_to_string(templates_dir.join("io.rs")).unwrap(), "bundled");
+        assert_eq!(manifest.get_template("io").unwrap().history.len(), 1);
+    }
+
+    #[test]
+    fn test_overwrite_replaces_directories_and_renames_are_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = Store::new(dir.path().join("source"));
+        let source_dir = source.templates_dir().unwrap();
+        fs::create_dir_all(source_dir.join("cli").join("src")).unwrap();
+        fs::write(source_dir.join("cli").join("src").join("main.rs"), "new").unwrap();
+        fs::write(source_dir.join("base.txt"), "{% block body %}{% endblock %}").unwrap();
+        fs::write(source_dir.join("child.txt"), "{% extends \"base\" %}").unwrap();
+        let mut cli = TemplateEntry::new("cli".to_string(), String::new());
+        cli.files = vec!["src/main.rs".to_string()];
+        let base = TemplateEntry::new("base.txt".to_string(), "txt".to_string());
+        let mut child = TemplateEntry::new("child.txt".to_string(), "txt".to_string());
+        child.extends = Some("base".to_string());
+        let path = dir.path().join("bundle.tar.gz");
+        let templates = [
+            ("cli", &cli, source_dir.as_path()),
+            ("base", &base, source_dir.as_path()),
+            ("child", &child, source_dir.as_path()),
+        ];
+        bundle::write(&path, &templates).unwrap();
+        let bundle = bundle::read(&path).unwrap();
+
+        let target = Store::new(dir.path().join("target"));
+        let templates_dir = target.templates_dir().unwrap();
+        fs::create_dir_all(templates_dir.join("cli")).unwrap();
+        fs::write(templates_dir.join("cli").join("old.rs"), "old").unwrap();
+        fs::write(templates_dir.join("base.txt"), "local base").unwrap();
+        let mut manifest = Manifest::new();
+        let mut old_cli = TemplateEntry::new("cli".to_string(), String::new());
+        old_cli.files = vec!["old.rs".to_string()];
+        manifest.add_template("cli".to_string(), old_cli);
+        manifest.add_template("base".to_string(), base.clone());
+
+        import(&bundle, ConflictPolicy::Overwrite, &target, &templates_dir, &mut manifest);
+        assert!(!templates_dir.join("cli").join("old.rs").exists());
+        assert_eq!(fs::read_to_string(templates_dir.join("cli").join("src").join("main.rs")).unwrap(), "new");
+        // Nothing is left over from staging
+        assert_eq!(fs::read_dir(&templates_dir).unwrap().count(), 3);
+
+        let other = Store::new(dir.path().join("other"));
+        let other_dir = other.templates_dir().unwrap();
+        fs::write(other_dir.join("base.txt"), "local base").unwrap();
+        let mut manifest = Manifest::new();
+        manifest.add_template("base".to_string(), base);
+        let outcomes = import(&bundle, ConflictPolicy::Rename, &other, &other_dir, &mut manifest);
+        assert_eq!(
+            stale_parents(&bundle, &outcomes),
+            [("child".to_string(), "base".to_string(), "base-2".to_string())]
+        );
+    }
+}
@@ -1,112 +1,158 @@
-use crate::config;
+use crate::config::{LoadedLayer, Stores};
+use crate::manifest::TemplateEntry;
+use crate::namespace::{self, TemplateName};
 use crate::error::AppError;
 use colored::*;
 use crate::output::OutputConfig;
 
 /// Handles the `tempo list` (or `tempo ls`) command.
 ///
+/// With a project store, each template is marked with the store it comes from, and global
+/// templates hidden by a local one of the same name are marked as shadowed.
+///
 /// # Returns
 /// * `Ok(())` if the templates were listed successfully or if no templates exist.
 /// * `Err(ListError)` if an error occurred.
-pub fn run(output: &OutputConfig) -> Result<(), AppError> {
-    let manifest = config::load_manifest()?;
-    output.verbose(format!("\t\t[VERBOSE] Manifest loaded. Contains {} templates.", manifest.templates.len()));
+pub fn run(stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let layers = stores.load()?;
+    let total: usize = layers.iter().map(|layer| layer.manifest.templates.len()).sum();
+    output.verbose(format!("\t\t[VERBOSE] Manifest loaded. Contains {} templates.", total));
     
     output.info(format!("\t{}", "Available templates:".blue().bold().underline()));
 
-    if manifest.templates.is_empty() {
+    if total == 0 {
         output.info(format!(
             "\t\t{}",
             "No templates found. Use 'tempo add <name> <path>' to add one.".yellow()
         ));
         return Ok(());
     }
-    // Sort entries by filename for consistent output
-    let mut template_names: Vec<_> = manifest.templates.keys().collect();
-    template_names.sort_by_key(|key| key.to_lowercase());
-
-    for template_name in template_names {
-        if let Some(entry) = manifest.templates.get(template_name){
-            if !entry.source_extension.is_empty() {
-                output.data(
-                    format!("\t\t- {} {}",
-                            template_name.cyan().bold(),
-                            format!("(.{})", entry.source_extension).dimmed()
-                    ));
-            } else {
-                output.data(format!("\t\t- {}", template_name.cyan().bold()));
-            }
-        }
+    for line in lines(&layers) {
+        output.data(line);
     }
 
     Ok(())
 }
 
+/// The listing itself: a line per template, under a header line per namespace.
+fn lines(layers: &[LoadedLayer]) -> Vec<String> {
+    // Sort entries by namespace, then name, for consistent output, the local one first when both stores have it
+    let mut templates: Vec<(&String, &TemplateEntry, usize)> = layers
+        .iter()
+        .enumerate()
+        .flat_map(|(index, layer)| layer.manifest.templates.iter().map(move |(name, entry)| (name, entry, index)))
+        .collect();
+    templates.sort_by_key(|(name, _, index)| {
+        let name = namespace::split(name);
+        (name.namespace.map(str::to_lowercase), name.name.to_lowercase(), *index)
+    });
+
+    // Templates without a namespace come first, then one group per namespace,
+    // compared without case like the sort, so `CP/a` and `cp/b` share one group
+    let mut lines = Vec::with_capacity(templates.len());
+    let mut current_namespace = None;
+    for (template_name, entry, index) in templates {
+        let TemplateName { namespace, name } = namespace::split(template_name);
+        if let Some(group) = namespace
+            && Some(group.to_lowercase()) != current_namespace
+        {
+            lines.push(format!("\t\t{}", format!("{group}{}", namespace::SEPARATOR).blue().bold()));
+            current_namespace = Some(group.to_lowercase());
+        }
+        let indent = if namespace.is_some() { "\t\t  " } else { "\t\t" };
+
+        let mut line = if entry.is_directory() {
+            format!("{indent}- {} {}",
+                    name.cyan().bold(),
+                    format!("(directory, {} files)", entry.files.len()).dimmed()
+            )
+        } else if !entry.source_extension.is_empty() {
+            format!("{indent}- {} {}",
+                    name.cyan().bold(),
+                    format!("(.{})", entry.source_extension).dimmed()
+            )
+        } else {
+            format!("{indent}- {}", name.cyan().bold())
+        };
+        if layers.len() > 1 {
+            let shadowed = layers[..index].iter().any(|layer| layer.manifest.get_template(template_name).is_some());
+            let layer = layers[index].layer;
+            let tag = if shadowed { format!("[{layer}, shadowed]") } else { format!("[{layer}]") };
+            line.push_str(&format!(" {}", tag.dimmed()));
+        }
+        lines.push(line);
+    }
+    lines
+}
+
 #[cfg(test)]
 mod tests {
     use super::*; // Import from outer module (list.rs)
-    use crate::config;
-    use std::fs::{self, File};
-    use std::path::Path;
-    
-    // Helper to create a dummy template file in the actual templates directory
-    fn create_template_in_actual_dir(templates_dir: &Path, filename: &str) {
-        File::create(templates_dir.join(filename))
-            .unwrap_or_else(|e| panic!("Failed to create dummy template {:?}: {}", filename, e));
+    use crate::config::Store;
+    use crate::manifest::Manifest;
+    use regex::Regex;
+    use tempfile::tempdir;
+
+    /// The listing without its colors.
+    fn plain_lines(stores: &Stores) -> Vec<String> {
+        let ansi = Regex::new(r"\x1b\[[0-9;]*m").unwrap();
+        lines(&stores.load().unwrap()).iter().map(|line| ansi.replace_all(line, "").into_owned()).collect()
     }
 
-    // Helper to clear the templates directory (use with caution, targeted removal is better)
-    fn clear_templates_dir(templates_dir: &Path) {
-        if templates_dir.exists() {
-            for entry in fs::read_dir(templates_dir).unwrap() {
-                let entry = entry.unwrap();
-                fs::remove_file(entry.path()).unwrap();
-            }
+    fn save(store: &Store, templates: &[(&str, &str)]) {
+        let mut manifest = Manifest::new();
+        for (name, extension) in templates {
+            let filename = if extension.is_empty() { name.to_string() } else { format!("{name}.{extension}") };
+            manifest.add_template(name.to_string(), TemplateEntry::new(filename, extension.to_string()));
         }
+        store.save_manifest(&manifest).unwrap();
     }
 
     #[test]
     fn test_list_no_templates() {
-        let templates_dir = config::get_templates_dir().unwrap();
-        clear_templates_dir(&templates_dir); // Ensure it's empty
+        // A temporary store, so the user's own templates are never touched
+        let root = tempdir().unwrap();
+        let stores = Stores { local: None, global: Store::new(root.path()) };
         let output = OutputConfig::new(true, false);
 
-        // We need to capture stdout to verify the printed output.
-        // This is a bit more involved. For now, let's just check Ok status.
-        // TODO: Implement stdout capturing for more thorough list tests.
-        let result = run(&output);
-        assert!(result.is_ok());
-        // Manual verification: run `cargo test -- --nocapture` and check output,
-        // or implement stdout capturing (e.g. using 'gag' crate or std::io::set_output_capture).
+        assert!(run(&stores, &output).is_ok());
+        assert!(plain_lines(&stores).is_empty());
     }
 
     #[test]
     fn test_list_with_templates() {
-        let templates_dir = config::get_templates_dir().unwrap();
-        let output = OutputConfig::new(true, false);
-        clear_templates_dir(&templates_dir); // Start clean
+        let root = tempdir().unwrap();
+        let store = Store::new(root.path());
+        save(&store, &[("beta", "rs"), ("alpha", "txt"), ("gamma_tpl", "")]);
 
-        create_template_in_actual_dir(&templates_dir, "alpha.txt");
-        create_template_in_actual_dir(&templates_dir, "beta.rs");
-        create_template_in_actual_dir(&templates_dir, "gamma_tpl"); // No extension
-
-        // TODO: Capture and assert stdout content.
-        let result = run(&output);
-        assert!(result.is_ok());
+        let stores = Stores { local: None, global: store };
+        assert_eq!(plain_lines(&stores), ["\t\t- alpha (.txt)", "\t\t- beta (.rs)", "\t\t- gamma_tpl"]);
+    }
 
-        // If we had stdout capture, we'd assert:
-        // captured_stdout.contains("- alpha (.txt)");
-        // captured_stdout.contains("- beta (.rs)");
-        // captured_stdout.contains("- gamma_tpl");
+    #[test]
+    fn test_list_groups_namespaces_and_marks_layers() {
+        let (local_root, global_root) = (tempdir().unwrap(), tempdir().unwrap());
+        let (local, global) = (Store::new(local_root.path()), Store::new(global_root.path()));
+        save(&local, &[("io", "rs"), ("CP/a", "cpp")]);
+        save(&global, &[("io", "py"), ("cp/b", "cpp"), ("CP/c", "")]);
+        let mut manifest = global.load_manifest().unwrap();
+        let mut cli = TemplateEntry::new("cli".to_string(), String::new());
+        cli.files = vec!["Cargo.toml".to_string(), "src/main.rs".to_string()];
+        manifest.add_template("cli".to_string(), cli);
+        global.save_manifest(&manifest).unwrap();
 
-        // Clean up
-        fs::remove_file(templates_dir.join("alpha.txt")).unwrap();
-        fs::remove_file(templates_dir.join("beta.rs")).unwrap();
-        fs::remove_file(templates_dir.join("gamma_tpl")).unwrap();
+        let stores = Stores { local: Some(local), global };
+        assert_eq!(
+            plain_lines(&stores),
+            [
+                "\t\t- cli (directory, 2 files) [global]",
+                "\t\t- io (.rs) [local]",
+                "\t\t- io (.py) [global, shadowed]",
+                "\t\tCP/",
+                "\t\t  - a (.cpp) [local]",
+                "\t\t  - b (.cpp) [global]",
+                "\t\t  - c [global]",
+            ]
+        );
     }
-
-    // Test for what happens if templates_dir doesn't exist initially
-    // This is harder to test because config::get_templates_dir() *creates* it.
-    // We'd need to mock that or test a lower-level function.
-    // For now, we trust that config::get_templates_dir handles creation.
 }
@@ -1,7 +1,17 @@
 pub mod add;
 pub mod apply;
+pub mod diff_cmd;
+pub mod doctor;
 pub mod edit;
+pub mod export;
+pub mod history_cmd;
+pub mod import;
 pub mod list;
 pub mod path_cmd;
+pub mod reindex;
 pub mod remove;
+pub mod restore;
 pub mod show;
+pub mod store_cmd;
+pub mod sync;
+pub mod undo;
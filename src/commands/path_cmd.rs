@@ -1,22 +1,20 @@
 use crate::cli::PathArgs; // Ensure this matches your CLI definition
-use crate::config;
+use crate::config::{self, Stores};
 use crate::error::AppError;
 use crate::output::OutputConfig;
 
 /// Handles the `tempo path` command.
-pub fn run(args: &PathArgs, output: &OutputConfig) -> Result<(), AppError> {
-    let manifest = config::load_manifest()?;
+pub fn run(args: &PathArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let layers = stores.load()?;
     output.verbose("[VERBOSE] Manifest loaded for 'path' command.");
-    // Find template entry in manifest
-    let template_entry = match manifest.get_template(&args.template_name) {
-        Some(entry) => entry,
+    // Find template entry in manifest, local store first
+    let (layer, template_entry) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
         None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
     };
 
-    // Get the actual filename and construct the path
-    let filename_in_storage = &template_entry.filename_in_storage;
-    let templates_dir = config::get_templates_dir()?;
-    let template_file_path = templates_dir.join(filename_in_storage);
+    // Construct the path of the stored file
+    let template_file_path = template_entry.stored_path(&layer.templates_dir);
 
     output.verbose(format!(
         "\t\t[VERBOSE] Path for template '{}' is: {:?}",
@@ -0,0 +1,291 @@
+use crate::cli::ReindexArgs;
+use crate::config::{ConfigError, Layer, Stores};
+use crate::error::AppError;
+use crate::frontmatter;
+use crate::lock;
+use crate::manifest::{Manifest, TemplateEntry};
+use crate::namespace;
+use crate::output::OutputConfig;
+use crate::scaffold;
+use crate::template;
+use colored::*;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Handles the `tempo reindex` command.
+///
+/// Rebuilds `manifest.toml` from the files under `templates/`, after copying the old
+/// manifest to `manifest.toml.bak`. Entries of an old manifest that still parses keep
+/// their metadata as long as their files are there.
+pub fn run(args: &ReindexArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let (layer, store) = match (&stores.local, args.local) {
+        (Some(local), true) => (Layer::Local, local),
+        (None, true) => return Err(AppError::NoProjectStore(std::env::current_dir()?)),
+        (_, false) => (Layer::Global, &stores.global),
+    };
+    output.info(format!(
+        "\t{} the {} store at {}...",
+        "→ Reindexing".blue().bold(),
+        layer,
+        format!("{:?}", store.root_path()).cyan()
+    ));
+
+    let _lock = lock::acquire(store, output)?;
+    let manifest_path = store.manifest_path();
+    let previous = match store.load_manifest() {
+        Ok(manifest) => Some(manifest),
+        Err(ConfigError::ManifestParseError { source_error, .. }) => {
+            output.warn(format!(
+                "\t\tWarning: The manifest does not parse ({}); rebuilding it from the stored files alone.",
+                source_error.message().trim()
+            ));
+            None
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if manifest_path.exists() {
+        let backup = store.manifest_backup_path();
+        fs::copy(&manifest_path, &backup).map_err(|e| AppError::FileCopy {
+            from: manifest_path.clone(),
+            to: backup.clone(),
+            source_error: e,
+        })?;
+        output.info(format!("\t\t{} Old manifest kept as {}", ">".magenta(), format!("{:?}", backup).cyan()));
+    }
+
+    let templates_dir = store.templates_dir()?;
+    let mut index = Index {
+        templates_dir: &templates_dir,
+        previous: previous.as_ref(),
+        directories: &args.directories,
+        manifest: Manifest::new(),
+        kept: 0,
+        skipped: Vec::new(),
+    };
+    index.walk("")?;
+    if let Some(previous) = &previous {
+        index.manifest.settings = previous.settings.clone();
+    }
+
+    for (path, reason) in &index.skipped {
+        output.warn(format!("\t\tWarning: Skipped '{path}': {reason}."));
+    }
+    if let Some(previous) = &previous {
+        let mut dropped: Vec<&String> =
+            previous.templates.keys().filter(|name| index.manifest.get_template(name).is_none()).collect();
+        dropped.sort();
+        for name in dropped {
+            output.warn(format!("\t\tWarning: Template '{name}' has no stored file any more and was dropped."));
+        }
+    }
+    let mut names: Vec<&String> = index.manifest.templates.keys().collect();
+    names.sort();
+    for name in names {
+        let entry = &index.manifest.templates[name];
+        output.verbose(format!(
+            "\t\t[VERBOSE] '{}' -> {}{}",
+            name,
+            entry.filename_in_storage,
+            if entry.is_directory() { format!(" ({} file(s))", entry.files.len()) } else { String::new() }
+        ));
+    }
+
+    store.save_manifest(&index.manifest)?;
+    output.success(format!(
+        "\t{} Indexed {} template(s), {} of them kept from the old manifest.",
+        "✓".green().bold(),
+        index.manifest.templates.len(),
+        index.kept
+    ));
+    Ok(())
+}
+
+/// Builds an entry for a stored template file, named the way `tempo add` stores it:
+/// `<name>.<ext>` becomes `name`, and `cp/segtree.cpp` becomes `cp/segtree`.
+///
+/// The description, variables and parent come from the file's front matter and
+/// `{% extends %}` tag, when it has them and they parse.
+pub fn file_entry(templates_dir: &Path, relative_path: &str) -> (String, TemplateEntry) {
+    let extension = Path::new(relative_path).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    let name = match relative_path.strip_suffix(extension).and_then(|rest| rest.strip_suffix('.')) {
+        Some(stem) if !extension.is_empty() => stem,
+        _ => relative_path,
+    };
+
+    let mut entry = TemplateEntry::new(relative_path.to_string(), extension.to_lowercase());
+    if let Ok(content) = fs::read_to_string(scaffold::join(templates_dir, relative_path))
+        && let Ok((front_matter, body)) = frontmatter::split(&content)
+    {
+        if let Some(front_matter) = front_matter {
+            front_matter.apply_to(&mut entry);
+        }
+        entry.extends = template::parent_of(body);
+    }
+    (name.to_string(), entry)
+}
+
+/// The state of one reindex: the manifest being built and the paths left out of it.
+struct Index<'a> {
+    templates_dir: &'a Path,
+    previous: Option<&'a Manifest>,
+    /// Stored directories to read as directory templates rather than namespaces.
+    directories: &'a [String],
+    manifest: Manifest,
+    kept: usize,
+    skipped: Vec<(String, String)>,
+}
+
+impl Index<'_> {
+    /// Indexes the files and directories in the namespace directory `relative_dir`.
+    fn walk(&mut self, relative_dir: &str) -> Result<(), AppError> {
+        let mut children: Vec<(String, bool)> = fs::read_dir(scaffold::join(self.templates_dir, relative_dir))?
+            .map(|child| {
+                let child = child?;
+                Ok((child.file_name().to_string_lossy().into_owned(), child.file_type()?.is_dir()))
+            })
+            .collect::<Result<_, std::io::Error>>()?;
+        children.sort();
+
+        for (file_name, is_dir) in children {
+            // Editor swap files and the like; hidden names cannot be templates
+            if file_name.starts_with('.') {
+                continue;
+            }
+            let relative_path =
+                if relative_dir.is_empty() { file_name } else { format!("{relative_dir}{}{file_name}", namespace::SEPARATOR) };
+
+            if let Some((name, entry)) = self.previous_entry(&relative_path, is_dir) {
+                let mut entry = entry.clone();
+                if is_dir {
+                    entry.files = scaffold::collect_files(&scaffold::join(self.templates_dir, &relative_path))?;
+                }
+                self.manifest.add_template(name.clone(), entry);
+                self.kept += 1;
+            } else if is_dir && !self.is_directory_template(&relative_path) {
+                self.walk(&relative_path)?;
+            } else if is_dir {
+                self.add(relative_path.clone(), directory_entry(self.templates_dir, &relative_path)?);
+            } else {
+                self.add(relative_path.clone(), file_entry(self.templates_dir, &relative_path));
+            }
+        }
+        Ok(())
+    }
+
+    /// The entry the old manifest has for `relative_path`, if it is of the same kind.
+    fn previous_entry(&self, relative_path: &str, is_dir: bool) -> Option<(&String, &TemplateEntry)> {
+        self.previous?
+            .templates
+            .iter()
+            .find(|(_, entry)| entry.filename_in_storage == relative_path && entry.is_directory() == is_dir)
+    }
+
+    /// Whether a stored directory holds one directory template rather than a namespace.
+    ///
+    /// Without a manifest, both look alike. A directory is read as a namespace unless
+    /// `--dir` names it, or some file in it could not be a template of its own: a
+    /// hidden file such as `.gitignore`, or two files with the same name but for the
+    /// extension.
+    fn is_directory_template(&self, relative_path: &str) -> bool {
+        if self.directories.iter().any(|dir| dir.trim_end_matches(namespace::SEPARATOR) == relative_path) {
+            return true;
+        }
+        // The old manifest knows better, when it stores templates inside the directory
+        let prefix = format!("{relative_path}{}", namespace::SEPARATOR);
+        if self.previous.is_some_and(|previous| {
+            previous.templates.values().any(|entry| entry.filename_in_storage.starts_with(&prefix))
+        }) {
+            return false;
+        }
+        !holds_only_templates(&scaffold::join(self.templates_dir, relative_path))
+    }
+
+    fn add(&mut self, relative_path: String, (name, entry): (String, TemplateEntry)) {
+        if let Err(reason) = namespace::parse(&name) {
+            self.skipped.push((relative_path, format!("'{name}' is not a valid template name ({reason})")));
+        } else if self.manifest.get_template(&name).is_some() {
+            self.skipped.push((relative_path, format!("another file is already indexed as '{name}'")));
+        } else {
+            self.manifest.add_template(name, entry);
+        }
+    }
+}
+
+/// Builds the entry for a stored directory template, named after its path.
+fn directory_entry(templates_dir: &Path, relative_path: &str) -> Result<(String, TemplateEntry), AppError> {
+    let mut entry = TemplateEntry::new(relative_path.to_string(), String::new());
+    entry.files = scaffold::collect_files(&scaffold::join(templates_dir, relative_path))?;
+    Ok((relative_path.to_string(), entry))
+}
+
+/// Whether every file under `dir` can be a single-file template with a name of its own.
+fn holds_only_templates(dir: &Path) -> bool {
+    let Ok(children) = fs::read_dir(dir) else {
+        return false;
+    };
+    let mut stems = HashSet::new();
+    for child in children {
+        let Ok(child) = child else {
+            return false;
+        };
+        let path = child.path();
+        let file_name = child.file_name().to_string_lossy().into_owned();
+        if file_name.starts_with('.') {
+            return false;
+        }
+        let stem = if path.is_dir() {
+            file_name.as_str()
+        } else {
+            path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default()
+        };
+        if namespace::parse(stem).is_err() || !stems.insert(stem.to_string()) {
+            return false;
+        }
+        if path.is_dir() && !holds_only_templates(&path) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_rebuilds_names_and_directory_templates() {
+        let dir = tempfile::tempdir().unwrap();
+        let templates_dir = dir.path();
+        fs::create_dir_all(templates_dir.join("cp")).unwrap();
+        fs::create_dir_all(templates_dir.join("rustcli").join("src")).unwrap();
+        fs::create_dir_all(templates_dir.join("web")).unwrap();
+        fs::write(templates_dir.join("io.RS"), "+++\ndescription = \"Fast IO\"\n+++\nfn main() {}").unwrap();
+        fs::write(templates_dir.join("cp").join("segtree.cpp"), "{% extends \"base\" %}").unwrap();
+        fs::write(templates_dir.join("rustcli").join(".gitignore"), "target").unwrap();
+        fs::write(templates_dir.join("rustcli").join("src").join("main.rs"), "fn main() {}").unwrap();
+        fs::write(templates_dir.join("web").join("index.html"), "").unwrap();
+        fs::write(templates_dir.join(".io.RS.swp"), "").unwrap();
+
+        let directories = vec!["web/".to_string()];
+        let mut index = Index {
+            templates_dir,
+            previous: None,
+            directories: &directories,
+            manifest: Manifest::new(),
+            kept: 0,
+            skipped: Vec::new(),
+        };
+        index.walk("").unwrap();
+
+        let manifest = &index.manifest;
+        assert_eq!(manifest.templates.len(), 4);
+        let io = manifest.get_template("io").unwrap();
+        assert_eq!((io.filename_in_storage.as_str(), io.source_extension.as_str()), ("io.RS", "rs"));
+        assert_eq!(io.description.as_deref(), Some("Fast IO"));
+        assert_eq!(manifest.get_template("cp/segtree").unwrap().extends.as_deref(), Some("base"));
+        assert_eq!(manifest.get_template("rustcli").unwrap().files, [".gitignore", "src/main.rs"]);
+        assert_eq!(manifest.get_template("web").unwrap().files, ["index.html"]);
+        assert!(index.skipped.is_empty());
+    }
+}
@@ -1,41 +1,67 @@
 use crate::cli::RemoveArgs;
-use crate::config;
+use crate::config::{self, Stores};
 use crate::error::AppError;
+use crate::git;
+use crate::lock;
 use crate::output::OutputConfig;
+use crate::scaffold;
 use colored::*;
-use std::fs;
 use std::io::{self, Write};
 
 /// Handles the `tempo remove` (or `tempo rm`) command.
-pub fn run(args: &RemoveArgs, force: bool, output: &OutputConfig) -> Result<(), AppError> {
+pub fn run(args: &RemoveArgs, force: bool, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
     output.info(format!(
         "\n\t{} template {}...",
         "→ Attempting to remove".yellow().bold(),
         args.template_name.cyan().bold()
     ));
 
-    let mut manifest = config::load_manifest()?;
-    output.verbose(format!("\t\t[VERBOSE] Manifest loaded. Contains {} templates before removal.", manifest.templates.len()));
-
-    let template_entry_to_remove = match manifest.get_template(&args.template_name) {
-        Some(entry) => entry.clone(), // Clone to avoid borrowing issues with manifest later
+    let layers = stores.load()?;
+    // Removes from the store the name resolves to, local first
+    let (layer, template_entry_to_remove) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
         None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
     };
+    let (store, templates_dir) = (&layer.store, &layer.templates_dir);
+    output.verbose(format!(
+        "\t\t[VERBOSE] Manifest of the {} store loaded. Contains {} templates before removal.",
+        layer.layer,
+        layer.manifest.templates.len()
+    ));
 
-    let filename_in_storage = template_entry_to_remove.filename_in_storage;
-    let templates_dir = config::get_templates_dir()?;
-    let template_file_path = templates_dir.join(&filename_in_storage);
+    let template_file_path = template_entry_to_remove.stored_path(templates_dir);
 
     output.verbose(format!(
         "\t\t[VERBOSE] Template '{}' corresponds to file: {:?}",
         args.template_name, template_file_path
     ));
-    
+
+    // Templates extending this one stop rendering once it is gone, unless another store has it too
+    let shadowed = layers
+        .iter()
+        .any(|other| other.layer != layer.layer && other.manifest.get_template(&args.template_name).is_some());
+    let mut children: Vec<&str> = layers
+        .iter()
+        .flat_map(|layer| &layer.manifest.templates)
+        .filter(|(_, entry)| entry.extends.as_deref() == Some(args.template_name.as_str()))
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !children.is_empty() && !shadowed {
+        children.sort();
+        children.dedup();
+        output.warn(format!(
+            "\t\tWarning: Template '{}' is extended by: {}. They will fail to render without it.",
+            args.template_name,
+            children.join(", ")
+        ));
+    }
+
     if !force {
         if !output.quiet {
             print!(
-                "\t❓ Are you sure you want to remove template '{}'? [y/N]: ",
-                args.template_name.cyan()
+                "\t❓ Are you sure you want to remove template '{}' from the {} store? [y/N]: ",
+                args.template_name.cyan(),
+                layer.layer
             );
             io::stdout().flush()?;
             let mut confirmation = String::new();
@@ -59,13 +85,22 @@ pub fn run(args: &RemoveArgs, force: bool, output: &OutputConfig) -> Result<(),
         ));
     }
 
+    // Reload under the lock: another command may have changed the manifest while we asked
+    let _lock = lock::acquire(store, output)?;
+    let mut manifest = store.load_manifest()?;
+    let (filename_in_storage, template_file_path) = match manifest.get_template(&args.template_name) {
+        Some(entry) => (entry.filename_in_storage.clone(), entry.stored_path(templates_dir)),
+        None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
+    };
+
     // Delete the actual template file
     if template_file_path.exists() {
-        fs::remove_file(&template_file_path).map_err(|e| AppError::FileRemove {
+        scaffold::remove_stored(&template_file_path).map_err(|e| AppError::FileRemove {
             path: template_file_path.clone(),
             source_error: e,
         })?;
         output.verbose(format!("\t\t[VERBOSE] Deleted file: {:?}", template_file_path));
+        scaffold::remove_empty_parents(&template_file_path, templates_dir);
     } else {
         output.warn(format!(
             "\t\tWarning: File '{}' not found in storage, but removing from manifest.",
@@ -78,8 +113,9 @@ pub fn run(args: &RemoveArgs, force: bool, output: &OutputConfig) -> Result<(),
     output.verbose(format!("\t\t[VERBOSE] Entry for '{}' removed from manifest object.", args.template_name));
 
     // Save the updated manifest
-    config::save_manifest(&manifest)?;
+    store.save_manifest(&manifest)?;
     output.verbose(format!("\t\t[VERBOSE] Manifest saved. Total templates: {}.", manifest.templates.len()));
+    git::autocommit(store.root_path(), &format!("tempo remove {}", args.template_name), output);
 
 
     output.success(
@@ -0,0 +1,92 @@
+use crate::atomic;
+use crate::cli::RestoreArgs;
+use crate::config::{self, Stores};
+use crate::error::AppError;
+use crate::frontmatter;
+use crate::git;
+use crate::history;
+use crate::lock;
+use crate::output::OutputConfig;
+use crate::scaffold;
+use crate::template;
+use chrono::Utc;
+use colored::*;
+use std::fs;
+
+/// Handles the `tempo restore` command.
+///
+/// Writes an earlier revision back as the template's content. The content it replaces
+/// becomes a revision itself, so a restore can be rolled back the same way.
+pub fn run(args: &RestoreArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    output.info(format!(
+        "\n\t{} template {} to revision {}...",
+        "→ Restoring".blue().bold(),
+        args.template_name.cyan().bold(),
+        args.rev
+    ));
+
+    let layers = stores.load()?;
+    let (layer, _) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
+        None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
+    };
+    let store = &layer.store;
+    let templates_dir = &layer.templates_dir;
+
+    let _lock = lock::acquire(store, output)?;
+    let mut manifest = store.load_manifest()?;
+    let Some(entry) = manifest.get_template_mut(&args.template_name) else {
+        return Err(AppError::TemplateNotFound(args.template_name.clone()));
+    };
+    if entry.is_directory() {
+        return Err(AppError::DirectoryTemplateUnsupported {
+            action: "restore".to_string(),
+            name: args.template_name.clone(),
+        });
+    }
+    let revision = history::revision(entry, &args.template_name, args.rev)?.clone();
+    let content = history::read_object(store, &args.template_name, &revision)?;
+
+    if let Some(current) = history::snapshot(store, entry, templates_dir)? {
+        output.verbose(format!("\t\t[VERBOSE] Current content kept as revision {}.", entry.history.len() + 1));
+        entry.history.push(current);
+    }
+
+    let old_path = entry.stored_path(templates_dir);
+    let new_path = scaffold::join(templates_dir, &revision.filename_in_storage);
+    if let Some(parent) = new_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    atomic::write(&new_path, &content)?;
+    if old_path != new_path && old_path.exists() {
+        output.verbose(format!("\t\t[VERBOSE] Removing {:?}, stored under another extension.", old_path));
+        fs::remove_file(&old_path).map_err(|e| AppError::FileRemove {
+            path: old_path.clone(),
+            source_error: e,
+        })?;
+    }
+
+    entry.filename_in_storage = revision.filename_in_storage.clone();
+    entry.source_extension = revision.source_extension.clone();
+    entry.updated_at = Utc::now();
+    // The restored content brings its own front matter and parent template
+    if let Ok(text) = std::str::from_utf8(&content)
+        && let Ok((front_matter, body)) = frontmatter::split(text)
+    {
+        if let Some(front_matter) = front_matter {
+            front_matter.apply_to(entry);
+        }
+        entry.extends = template::parent_of(body);
+    }
+    store.save_manifest(&manifest)?;
+    git::autocommit(store.root_path(), &format!("tempo restore {} --rev {}", args.template_name, args.rev), output);
+
+    output.success(format!(
+        "\t{} Template '{}' restored to revision {} from {}.",
+        "✓".green().bold(),
+        args.template_name.yellow().bold(),
+        args.rev,
+        revision.saved_at.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S")
+    ));
+    Ok(())
+}
@@ -1,37 +1,75 @@
-use crate::{cli::ShowArgs, config, error::AppError};
+use crate::config::{self, LoadedLayer, Stores};
+use crate::{cli::ShowArgs, context, error::AppError, frontmatter, prompt};
+use crate::loader::StoreLoader;
+use crate::manifest::TemplateEntry;
+use crate::schema::VariableSpec;
+use crate::namespace;
+use crate::scaffold;
+use colored::*;
 use atty;
 use std::fs;
+use std::path::Path;
 use crate::output::OutputConfig;
 
 /// Handles the `tempo show` command.
-pub fn run(args: &ShowArgs, output: &OutputConfig) -> Result<(), AppError> {
-    let manifest = config::load_manifest()?;
+pub fn run(args: &ShowArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let layers = stores.load()?;
     output.verbose("[VERBOSE] Manifest loaded for 'show' command.");
     
-    let template_entry = match manifest.get_template(&args.template_name) {
-        Some(entry) => entry,
+    let (layer, template_entry) = match config::find_template(&layers, &args.template_name) {
+        Some(found) => found,
         None => return Err(AppError::TemplateNotFound(args.template_name.clone())),
     };
 
     let filename_in_storage = &template_entry.filename_in_storage;
-    let templates_dir = config::get_templates_dir()?; // Ensure templates dir path is available
-    output.verbose(format!("[VERBOSE] Showing template '{}' from: {:?}", args.template_name, templates_dir));
-    let template_file_path = templates_dir.join(filename_in_storage);
+    output.verbose(format!(
+        "[VERBOSE] Showing template '{}' from the {} store: {:?}",
+        args.template_name, layer.layer, layer.templates_dir
+    ));
+    let template_file_path = template_entry.stored_path(&layer.templates_dir);
     output.verbose(format!("[VERBOSE] Template file found at: {:?}", template_file_path));
 
+    if template_entry.is_directory() {
+        return show_directory(args, template_entry, &template_file_path, &layers, output);
+    }
+
     let content = fs::read_to_string(&template_file_path).map_err(|io_err| {
         // If file not found here, it implies inconsistency between manifest and filesystem
         if io_err.kind() == std::io::ErrorKind::NotFound {
             AppError::TemplateFileMissing {
                 name: args.template_name.clone(),
-                path: template_file_path, 
+                path: template_file_path.clone(),
             }
         } else {
             AppError::Io(io_err) 
         }
     })?;
     
-    output.data_no_nl(format!("{}", content));
+    // The front matter is metadata, not part of the template's output
+    let (front_matter, body) = frontmatter::split(&content).map_err(|reason| AppError::InvalidFrontMatter {
+        path: template_file_path.clone(),
+        reason,
+    })?;
+    let variables = front_matter
+        .as_ref()
+        .map_or(&template_entry.variables, |fm| &fm.variables);
+
+    if args.params {
+        print_params(&args.template_name, variables, output);
+        return Ok(());
+    }
+
+    let content = if args.render {
+        // Render as if applying to a file named like the stored template in the current directory
+        let dest_path = Path::new(namespace::split(filename_in_storage).name);
+        let mut vars = context::template_variables(&args.template_name, template_entry, dest_path, &args.set);
+        let loader = StoreLoader::new(&layers);
+        prompt::render_with_prompts(&args.template_name, body, variables, &mut vars, &loader, output)?
+    } else {
+        body.to_string()
+    };
+
+    output.data_no_nl(&content);
 
     if atty::is(atty::Stream::Stdout) && !content.ends_with('\n') {
         println!(); // Add a newline if outputting to terminal and content doesn't have one
@@ -39,3 +77,84 @@ pub fn run(args: &ShowArgs, output: &OutputConfig) -> Result<(), AppError> {
 
     Ok(())
 }
+
+/// Shows a directory template: its file list, or every file rendered under a header.
+fn show_directory(
+    args: &ShowArgs,
+    template_entry: &TemplateEntry,
+    stored_dir: &Path,
+    layers: &[LoadedLayer],
+    output: &OutputConfig,
+) -> Result<(), AppError> {
+    if args.params {
+        print_params(&args.template_name, &template_entry.variables, output);
+        return Ok(());
+    }
+    if !args.render {
+        for file in &template_entry.files {
+            output.data(file);
+        }
+        return Ok(());
+    }
+
+    // Render as if applying to a directory named like the template in the current directory
+    let dest_dir = Path::new(namespace::split(&template_entry.filename_in_storage).name);
+    let mut vars = context::template_variables(&args.template_name, template_entry, dest_dir, &args.set);
+    prompt::resolve_declared(&args.template_name, &template_entry.variables, &mut vars, output)?;
+    let loader = StoreLoader::new(layers);
+
+    for file in &template_entry.files {
+        let specs = &template_entry.variables;
+        let Some(dest_file) =
+            scaffold::render_relative_path(&args.template_name, file, specs, &mut vars, &loader, output)?
+        else {
+            continue; // Left out by its path
+        };
+        output.data(format!("==> {dest_file} <=="));
+        let stored_path = scaffold::join(stored_dir, file);
+        let Ok(source) = fs::read_to_string(&stored_path) else {
+            output.data("(binary or unreadable file)");
+            continue;
+        };
+        context::update_file_variables(&mut vars, &scaffold::join(dest_dir, &dest_file), &args.set);
+        let name = format!("{}/{}", args.template_name, file);
+        let content = prompt::render_resolved(&name, &source, specs, &mut vars, &loader, output)?;
+        output.data_no_nl(&content);
+        if !content.ends_with('\n') {
+            output.data("");
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints the variable schema of a template, one variable per line.
+fn print_params(template_name: &str, specs: &[VariableSpec], output: &OutputConfig) {
+    output.info(format!("\t{} '{}':", "Parameters of".blue().bold(), template_name.cyan()));
+
+    if specs.is_empty() {
+        output.info(format!("\t\t{}", "This template declares no parameters.".yellow()));
+        return;
+    }
+
+    for spec in specs {
+        let mut line = format!("\t\t- {} {}", spec.name.cyan().bold(), format!("<{}>", spec.kind).dimmed());
+        if spec.required {
+            line.push_str(&format!(" {}", "required".red()));
+        }
+        if let Some(description) = &spec.description {
+            line.push_str(&format!(": {description}"));
+        }
+        output.data(line);
+
+        if let Some(default) = spec.default_text() {
+            output.data(format!("\t\t    default: {default}"));
+        }
+        if !spec.choices.is_empty() {
+            output.data(format!("\t\t    choices: {}", spec.choices.join(", ")));
+        }
+        if let Some(pattern) = &spec.pattern {
+            output.data(format!("\t\t    pattern: {pattern}"));
+        }
+    }
+}
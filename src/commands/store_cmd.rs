@@ -0,0 +1,47 @@
+use crate::cli::{StoreArgs, StoreCommands, StoreInitArgs};
+use crate::config::Stores;
+use crate::error::AppError;
+use crate::git;
+use crate::lock;
+use crate::output::OutputConfig;
+use colored::*;
+
+/// Handles the `tempo store` command.
+pub fn run(args: &StoreArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    match &args.command {
+        StoreCommands::Init(init_args) => init(init_args, stores, output),
+    }
+}
+
+/// Creates the global store's directories and manifest, and with `--git` makes it a git
+/// repository holding everything but the machine-local files.
+fn init(args: &StoreInitArgs, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let store = &stores.global;
+    output.info(format!(
+        "\t{} the template store at {}...",
+        "→ Initializing".blue().bold(),
+        format!("{:?}", store.root_path()).cyan()
+    ));
+
+    let _lock = lock::acquire(store, output)?;
+    let root = store.root()?;
+    store.templates_dir()?;
+    if !store.manifest_path().exists() {
+        store.save_manifest(&store.load_manifest()?)?;
+    }
+
+    if args.git {
+        let created = git::init(&root, args.remote.as_deref())?;
+        output.info(format!(
+            "\t\t{} {}",
+            ">".magenta(),
+            if created { "Created a git repository; add, edit and remove now commit to it." } else { "The store is already a git repository." }
+        ));
+        if let Some(remote) = &args.remote {
+            output.info(format!("\t\t{} Syncing with {}", ">".magenta(), remote.cyan()));
+        }
+    }
+
+    output.success(format!("\t{} Template store ready.", "✓".green().bold()));
+    Ok(())
+}
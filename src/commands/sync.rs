@@ -0,0 +1,35 @@
+use crate::config::Stores;
+use crate::error::AppError;
+use crate::git;
+use crate::lock;
+use crate::output::OutputConfig;
+use colored::*;
+
+/// Handles the `tempo sync` command.
+///
+/// Commits pending changes of the global store, rebases them onto the remote's and
+/// pushes the result. Concurrent changes of the manifest are merged by template name.
+pub fn run(stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let store = &stores.global;
+    output.info(format!(
+        "\t{} the template store at {}...",
+        "→ Syncing".blue().bold(),
+        format!("{:?}", store.root_path()).cyan()
+    ));
+
+    let _lock = lock::acquire(store, output)?;
+    let report = git::sync(&store.root()?)?;
+    if report.merged_manifest {
+        output.info(format!(
+            "\t\t{} Both sides changed the manifest; merged it template by template.",
+            ">".magenta()
+        ));
+    }
+    output.success(format!(
+        "\t{} Pulled {} and pushed {} commit(s).",
+        "✓ Synced.".green().bold(),
+        report.pulled,
+        report.pushed
+    ));
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use crate::cli::UndoArgs;
+use crate::config::Stores;
+use crate::error::AppError;
+use crate::journal::{Journal, JournalEntry};
+use crate::lock;
+use crate::output::OutputConfig;
+use chrono::Local;
+use colored::*;
+use std::io::{self, Write};
+
+/// Handles the `tempo undo` command.
+pub fn run(args: &UndoArgs, force: bool, stores: &Stores, output: &OutputConfig) -> Result<(), AppError> {
+    let store = &stores.global;
+    let journal = Journal::load(store)?;
+    output.verbose(format!("\t\t[VERBOSE] Journal loaded. Contains {} applies.", journal.entries.len()));
+
+    if args.list {
+        return list(&journal, output);
+    }
+
+    if journal.entries.is_empty() {
+        output.info(format!("\t{}", "Nothing to undo: no applies are recorded.".yellow()));
+        return Ok(());
+    }
+    let count = (args.count as usize).min(journal.entries.len());
+    if count < args.count as usize {
+        output.warn(format!("\t\tWarning: Only {count} apply(ies) are recorded."));
+    }
+
+    output.info(format!("\n\t{} the last {} apply(ies):", "→ Undoing".yellow().bold(), count));
+    for entry in journal.entries.iter().rev().take(count) {
+        describe(entry, output);
+    }
+
+    if !force {
+        if !output.quiet {
+            print!("\t❓ Restore these files to their state before the apply? [y/N]: ");
+            io::stdout().flush()?;
+            let mut confirmation = String::new();
+            io::stdin().read_line(&mut confirmation)?;
+            if confirmation.trim().to_lowercase() != "y" {
+                output.info(format!("\t{} Undo cancelled by user.", "✗".dimmed()));
+                return Ok(());
+            }
+        } else {
+            let last = journal.entries.last().map(|entry| entry.template.clone()).unwrap_or_default();
+            return Err(AppError::ConfirmationNeededInQuietMode {
+                action: "undo".to_string(),
+                template_name: last,
+            });
+        }
+    } else {
+        output.info(format!(
+            "\t\t{} {}",
+            ">".magenta(),
+            "Force flag active, skipping confirmation.".bright_yellow()
+        ));
+    }
+
+    // Reload under the lock, and only revert the applies that were shown
+    let shown: Vec<String> = journal.entries.iter().rev().take(count).map(|entry| entry.id.clone()).collect();
+    let _lock = lock::acquire(store, output)?;
+    let mut journal = Journal::load(store)?;
+    let mut undone = 0;
+    for id in &shown {
+        if journal.entries.last().map(|entry| &entry.id) != Some(id) {
+            output.warn("\t\tWarning: The journal changed while waiting for confirmation; stopping early.");
+            break;
+        }
+        if let Some(entry) = journal.undo_last(args.discard_changes)? {
+            output.verbose(format!(
+                "\t\t[VERBOSE] Reverted apply {} of '{}' ({} file(s)).",
+                entry.id,
+                entry.template,
+                entry.files.len()
+            ));
+            undone += 1;
+        }
+    }
+
+    output.success(format!("\n\t{} Undid {} apply(ies).", "✓ Successfully".green().bold(), undone));
+    Ok(())
+}
+
+/// Prints the recorded applies, newest first.
+fn list(journal: &Journal, output: &OutputConfig) -> Result<(), AppError> {
+    output.info(format!("\t{}", "Recorded applies (newest first):".blue().bold().underline()));
+    if journal.entries.is_empty() {
+        output.info(format!("\t\t{}", "No applies are recorded.".yellow()));
+        return Ok(());
+    }
+    for (index, entry) in journal.entries.iter().rev().enumerate() {
+        let mut line = format!(
+            "\t\t{} {} {}",
+            format!("{}.", index + 1).dimmed(),
+            entry.applied_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            entry.template.cyan().bold()
+        );
+        if let Some(strategy) = &entry.strategy {
+            line.push_str(&format!(" {}", format!("({strategy})").dimmed()));
+        }
+        if entry.pending {
+            line.push_str(&format!(" {}", "[incomplete]".red()));
+        }
+        output.data(line);
+        for file in &entry.files {
+            output.data(format!("\t\t    {} {}", format!("{:<13}", file.action).magenta(), file.path.display()));
+        }
+    }
+    Ok(())
+}
+
+/// Shows which files an undo of `entry` touches.
+fn describe(entry: &JournalEntry, output: &OutputConfig) {
+    output.info(format!(
+        "\t\t- {} {}",
+        entry.template.cyan().bold(),
+        entry.applied_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string().dimmed()
+    ));
+    for file in &entry.files {
+        let what = if file.backup.is_some() { "restore" } else { "delete" };
+        output.info(format!("\t\t    {} {}", format!("{what:<8}").magenta(), file.path.display()));
+    }
+}
@@ -1,9 +1,13 @@
-use std::fs::{self, File, OpenOptions};
-use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
-use crate::manifest::Manifest;
-use toml;
+use crate::atomic;
+use crate::lock;
+use crate::manifest::{self, FormatError, MANIFEST_VERSION, Manifest, TemplateEntry};
+use crate::output::OutputConfig;
+use colored::*;
 
 #[allow(dead_code)]
 #[derive(Debug, Error)]
@@ -28,7 +32,7 @@ pub enum ConfigError {
         source_error: io::Error,
     },
 
-    #[error("Failed to parse manifest file at {path:?}: {source_error}")]
+    #[error("Failed to parse manifest file at {path:?}: {source_error}\nRun 'tempo reindex' to rebuild it from the stored templates.")]
     ManifestParseError {
         path: PathBuf,
         #[source]
@@ -40,68 +44,272 @@ pub enum ConfigError {
         #[source]
         source_error: toml::ser::Error, // Error from toml serialization
     },
+
+    #[error("The manifest at {path:?} is in format version {version}, but this tempo only reads up to version {supported}. Upgrade tempo to use this store.")]
+    ManifestTooNew {
+        path: PathBuf,
+        version: i64,
+        supported: u32,
+    },
 }
 
 const APP_NAME: &str = "tempo";
 const MANIFEST_FILENAME: &str = "manifest.toml";
+const TEMPLATES_DIR_NAME: &str = "templates";
 
-/// Gets the application's base configuration directory.
-/// This is typically ~/.config/tempo/ on Linux/macOS
-/// or %APPDATA%\tempo\ on Windows.
-///
-/// It will attempt to create this directory if it doesn't exist.
-///
-/// # Errors
+/// Environment variable naming the store root, used when `--store` is not given.
+pub const HOME_ENV: &str = "TEMPO_HOME";
+
+/// Name of the directory holding a project's own store, e.g. `my-repo/.tempo/`.
+pub const LOCAL_STORE_DIR: &str = ".tempo";
+
+/// The root directory holding the templates, the manifest, the journal and the lock file.
 ///
-/// Returns `ConfigError::NoConfigDirectory` if the system's config directory cannot be determined.
-/// Returns `ConfigError::DirectoryCreationError` if the directory cannot be created.
-pub fn get_app_config_dir() -> Result<PathBuf, ConfigError> {
-    match dirs::config_dir() {
-        Some(mut path) => {
-            path.push(APP_NAME);
-            if !path.exists() {
-                fs::create_dir_all(&path)
-                    .map_err(|e| ConfigError::DirectoryCreationError(path.clone(), e))?;
-            }
-            Ok(path)
+/// Resolved once in `main` and passed to every command, so nothing else decides where
+/// the store lives.
+#[derive(Debug, Clone)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    /// A store rooted at `root`. Nothing is created until a path is asked for.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Store { root: root.into() }
+    }
+
+    /// Picks the store root: the `--store` flag, then `TEMPO_HOME`, then the app's
+    /// configuration directory (~/.config/tempo/ on Linux/macOS, %APPDATA%\tempo\ on Windows).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::NoConfigDirectory` if neither is given and the system's config
+    /// directory cannot be determined.
+    pub fn resolve(flag: Option<&Path>) -> Result<Self, ConfigError> {
+        let root = match flag {
+            Some(path) => path.to_path_buf(),
+            None => match std::env::var_os(HOME_ENV).filter(|home| !home.is_empty()) {
+                Some(home) => PathBuf::from(home),
+                None => dirs::config_dir().ok_or(ConfigError::NoConfigDirectory)?.join(APP_NAME),
+            },
+        };
+        // Absolute, so paths recorded in the journal still work from another directory
+        let root = std::path::absolute(&root).unwrap_or(root);
+        Ok(Store::new(root))
+    }
+
+    /// The store's root directory, without touching the disk.
+    pub fn root_path(&self) -> &Path {
+        &self.root
+    }
+
+    /// The store's root directory, created if it doesn't exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConfigError::DirectoryCreationError` if the directory cannot be created.
+    pub fn root(&self) -> Result<PathBuf, ConfigError> {
+        ensure_dir(self.root.clone())
+    }
+
+    /// Gets the directory where templates are stored, e.g. ~/.config/tempo/templates/
+    ///
+    /// It will attempt to create this directory (and its parent) if it doesn't exist.
+    pub fn templates_dir(&self) -> Result<PathBuf, ConfigError> {
+        ensure_dir(self.root.join(TEMPLATES_DIR_NAME))
+    }
+
+    /// Gets the path of the lock file taken around changes to the manifest and the journal,
+    /// e.g. ~/.config/tempo/tempo.lock
+    pub fn lock_path(&self) -> Result<PathBuf, ConfigError> {
+        Ok(self.root()?.join("tempo.lock"))
+    }
+
+    /// Gets the directory holding the apply journal and its file backups,
+    /// e.g. ~/.config/tempo/journal/
+    ///
+    /// It will attempt to create this directory (and its parent) if it doesn't exist.
+    pub fn journal_dir(&self) -> Result<PathBuf, ConfigError> {
+        ensure_dir(self.root.join("journal"))
+    }
+
+    /// Gets the directory holding earlier revisions of templates, named by content hash,
+    /// e.g. ~/.config/tempo/objects/
+    ///
+    /// It will attempt to create this directory (and its parent) if it doesn't exist.
+    pub fn objects_dir(&self) -> Result<PathBuf, ConfigError> {
+        ensure_dir(self.root.join("objects"))
+    }
+
+    /// Gets the full path to the manifest file (e.g., manifest.toml).
+    pub fn manifest_path(&self) -> PathBuf {
+        self.root.join(MANIFEST_FILENAME)
+    }
+
+    /// Gets the path the manifest is copied to before it is rewritten wholesale, by a
+    /// format migration or `tempo reindex`, e.g. ~/.config/tempo/manifest.toml.bak
+    pub fn manifest_backup_path(&self) -> PathBuf {
+        self.root.join(format!("{MANIFEST_FILENAME}.bak"))
+    }
+
+    /// Loads the manifest from the manifest file.
+    /// If the file doesn't exist, returns a new empty Manifest.
+    ///
+    /// A manifest in an older format is migrated, and written back in the current one
+    /// when no other command holds the store.
+    pub fn load_manifest(&self) -> Result<Manifest, ConfigError> {
+        let (manifest, migrated_from) = load_manifest(&self.manifest_path())?;
+        if migrated_from.is_some() {
+            self.save_migrated_manifest();
+        }
+        Ok(manifest)
+    }
+
+    /// Rewrites an old-format manifest in the current format, keeping the old file as a
+    /// backup. Best effort: a command holding the lock saves the new format itself, and the
+    /// manifest is read again under the lock so no concurrent change is lost.
+    fn save_migrated_manifest(&self) {
+        let Ok(Some(_lock)) = self.lock_path().map(|path| lock::try_acquire(&path).ok().flatten()) else {
+            return;
+        };
+        let manifest_path = self.manifest_path();
+        if let Ok((manifest, Some(_))) = load_manifest(&manifest_path)
+            && fs::copy(&manifest_path, self.manifest_backup_path()).is_ok()
+        {
+            let _ = save_manifest(&manifest_path, &manifest);
+        }
+    }
+
+    /// Saves the given Manifest data to the manifest file.
+    /// This will overwrite the existing manifest file.
+    pub fn save_manifest(&self, manifest: &Manifest) -> Result<(), ConfigError> {
+        self.root()?;
+        save_manifest(&self.manifest_path(), manifest)
+    }
+}
+
+/// Which store a template comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// The `.tempo/` store of the project around the current directory.
+    Local,
+    /// The user's store: `--store`, `TEMPO_HOME` or ~/.config/tempo.
+    Global,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Layer::Local => write!(f, "local"),
+            Layer::Global => write!(f, "global"),
         }
-        None => Err(ConfigError::NoConfigDirectory),
     }
 }
 
-/// Gets the directory where templates will be stored.
-/// This is typically ~/.config/tempo/templates/
-///
-/// It will attempt to create this directory (and its parent) if it doesn't exist.
-///
-/// # Errors
+/// The project store, if there is one, layered over the global store.
 ///
-/// Returns `ConfigError` if the base config directory cannot be determined or
-/// if the templates directory cannot be created.
-pub fn get_templates_dir() -> Result<PathBuf, ConfigError> {
-    let mut path = get_app_config_dir()?;
-    path.push("templates");
-    if !path.exists() {
-        fs::create_dir_all(&path)
-            .map_err(|e| ConfigError::DirectoryCreationError(path.clone(), e))?;
+/// Template names resolve local-first, so a project can override a global template.
+#[derive(Debug, Clone)]
+pub struct Stores {
+    pub local: Option<Store>,
+    pub global: Store,
+}
+
+impl Stores {
+    /// Resolves the global store (see `Store::resolve`) and looks for a project store
+    /// from the current directory up.
+    pub fn resolve(flag: Option<&Path>) -> Result<Self, ConfigError> {
+        let global = Store::resolve(flag)?;
+        let local = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| find_local_store(&cwd))
+            .filter(|root| root != global.root_path()) // `--store .tempo` is not layered over itself
+            .map(Store::new);
+        Ok(Stores { local, global })
     }
-    Ok(path)
+
+    /// The store new templates go to: the project's with `local`, creating `.tempo/` in the
+    /// current directory if there is none, else the global one.
+    pub fn target(&self, local: bool, output: &OutputConfig) -> io::Result<Store> {
+        Ok(match (&self.local, local) {
+            (Some(local), true) => local.clone(),
+            (None, true) => {
+                let root = std::env::current_dir()?.join(LOCAL_STORE_DIR);
+                output.info(format!(
+                    "\t\t{} Creating a project store at {}",
+                    ">".magenta(),
+                    format!("{:?}", root).cyan()
+                ));
+                Store::new(root)
+            }
+            (_, false) => self.global.clone(),
+        })
+    }
+
+    /// The stores in lookup order: local first, then global.
+    pub fn layers(&self) -> Vec<(Layer, &Store)> {
+        let mut layers = Vec::with_capacity(2);
+        if let Some(local) = &self.local {
+            layers.push((Layer::Local, local));
+        }
+        layers.push((Layer::Global, &self.global));
+        layers
+    }
+
+    /// Loads the manifest of every store, in lookup order.
+    pub fn load(&self) -> Result<Vec<LoadedLayer>, ConfigError> {
+        self.layers()
+            .into_iter()
+            .map(|(layer, store)| {
+                Ok(LoadedLayer {
+                    layer,
+                    manifest: store.load_manifest()?,
+                    templates_dir: store.templates_dir()?,
+                    store: store.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A store with its manifest loaded.
+#[derive(Debug)]
+pub struct LoadedLayer {
+    pub layer: Layer,
+    pub store: Store,
+    pub manifest: Manifest,
+    pub templates_dir: PathBuf,
 }
 
-/// Gets the full path to the manifest file (e.g., manifest.toml).
-pub fn get_manifest_path() -> Result<PathBuf, ConfigError> {
-    let mut path = get_app_config_dir()?;
-    path.push(MANIFEST_FILENAME);
+/// Finds a template by name, in the first layer that has it.
+pub fn find_template<'a>(layers: &'a [LoadedLayer], name: &str) -> Option<(&'a LoadedLayer, &'a TemplateEntry)> {
+    layers
+        .iter()
+        .find_map(|layer| layer.manifest.get_template(name).map(|entry| (layer, entry)))
+}
+
+/// Walks up from `start` looking for a `.tempo/` directory holding a store, i.e. its own
+/// `manifest.toml` or `templates/`. Any other `.tempo/` is not ours and is passed over.
+pub fn find_local_store(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(LOCAL_STORE_DIR))
+        .find(|candidate| candidate.join(MANIFEST_FILENAME).is_file() || candidate.join(TEMPLATES_DIR_NAME).is_dir())
+}
+
+fn ensure_dir(path: PathBuf) -> Result<PathBuf, ConfigError> {
+    if !path.exists() {
+        fs::create_dir_all(&path).map_err(|e| ConfigError::DirectoryCreationError(path.clone(), e))?;
+    }
     Ok(path)
 }
 
-/// Loads the manifest from the manifest file.
-/// If the file doesn't exist, returns a new empty Manifest.
-pub fn load_manifest() -> Result<Manifest, ConfigError> {
-    let manifest_path = get_manifest_path()?;
+/// Reads the manifest file, returning the format version it was migrated from, if any.
+fn load_manifest(manifest_path: &Path) -> Result<(Manifest, Option<u32>), ConfigError> {
+    let manifest_path = manifest_path.to_path_buf();
 
     if !manifest_path.exists() {
-        return Ok(Manifest::new());
+        return Ok((Manifest::new(), None));
     }
 
     let mut file_content = String::new();
@@ -117,41 +325,34 @@ pub fn load_manifest() -> Result<Manifest, ConfigError> {
         })?;
 
     if file_content.trim().is_empty() {
-        return Ok(Manifest::new());
+        return Ok((Manifest::new(), None));
     }
 
-    toml::from_str(&file_content).map_err(|e| ConfigError::ManifestParseError {
-        path: manifest_path, // No clone needed here as it's the last use
-        source_error: e,
+    manifest::parse(&file_content).map_err(|e| match e {
+        FormatError::Toml(e) => ConfigError::ManifestParseError {
+            path: manifest_path, // No clone needed here as it's the last use
+            source_error: e,
+        },
+        FormatError::TooNew(version) => ConfigError::ManifestTooNew {
+            path: manifest_path,
+            version,
+            supported: MANIFEST_VERSION,
+        },
     })
 }
 
-/// Saves the given Manifest data to the manifest file.
-/// This will overwrite the existing manifest file.
-pub fn save_manifest(manifest: &Manifest) -> Result<(), ConfigError> {
-    let manifest_path = get_manifest_path()?;
+fn save_manifest(manifest_path: &Path, manifest: &Manifest) -> Result<(), ConfigError> {
 
     let toml_string =
         toml::to_string_pretty(manifest).map_err(|e| ConfigError::ManifestSerializeError {
             source_error: e,
         })?;
 
-    // Write to the file, creating it if it doesn't exist, truncating if it does.
-    let mut file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true) // Overwrite existing content
-        .open(&manifest_path)
-        .map_err(|e| ConfigError::ManifestWriteError {
-            path: manifest_path.clone(),
-            source_error: e,
-        })?;
-
-    file.write_all(toml_string.as_bytes())
-        .map_err(|e| ConfigError::ManifestWriteError {
-            path: manifest_path, // No clone needed here
-            source_error: e,
-        })?;
+    // Write a temporary file and rename it into place, so a crash never leaves half a manifest
+    atomic::write(manifest_path, toml_string.as_bytes()).map_err(|e| ConfigError::ManifestWriteError {
+        path: manifest_path.to_path_buf(),
+        source_error: e,
+    })?;
 
     Ok(())
 }
@@ -160,50 +361,91 @@ pub fn save_manifest(manifest: &Manifest) -> Result<(), ConfigError> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs;
+    use crate::manifest::TemplateEntry;
     use tempfile::tempdir; // For creating temporary directories for tests
 
-    // Helper to set up a temporary isolated environment for config tests
-    #[allow(dead_code)]
-    fn setup_test_env() -> (tempfile::TempDir, PathBuf) {
-        let temp_home = tempdir().expect("Failed to create temp dir for home");
-        // let fake_config_path = temp_home.path().join(".config"); // Simulate ~/.config
+    #[test]
+    fn test_templates_dir_creates_if_not_exists() {
+        let temp_root = tempdir().unwrap();
+        let store = Store::new(temp_root.path().join(APP_NAME));
+
+        let templates_path = store.templates_dir().unwrap();
+        assert_eq!(templates_path, temp_root.path().join(APP_NAME).join("templates"));
+        assert!(templates_path.is_dir());
+    }
+
+    #[test]
+    fn test_store_flag_wins_and_manifest_round_trips() {
+        let temp_root = tempdir().unwrap();
+        let store = Store::resolve(Some(temp_root.path())).unwrap();
+        assert_eq!(store.manifest_path(), temp_root.path().join(MANIFEST_FILENAME));
 
-        let app_base_dir = temp_home.path().join(APP_NAME);
-        fs::create_dir_all(&app_base_dir).expect("Failed to create temp app base dir");
-        (temp_home, app_base_dir)
+        assert!(store.load_manifest().unwrap().templates.is_empty());
+        let mut manifest = Manifest::new();
+        manifest.add_template("io".to_string(), TemplateEntry::new("io.rs".to_string(), "rs".to_string()));
+        store.save_manifest(&manifest).unwrap();
+        assert!(store.load_manifest().unwrap().get_template("io").is_some());
     }
 
     #[test]
-    fn test_get_templates_dir_creates_if_not_exists() {
-        // A simpler test: if we manually create the structure, does it find it?
+    fn test_flat_manifest_is_migrated_and_newer_ones_refused() {
         let temp_root = tempdir().unwrap();
-        let app_config = temp_root.path().join(APP_NAME);
-        fs::create_dir_all(&app_config).unwrap();
-        let templates_dir_expected = app_config.join("templates");
-
-        // We can't directly call `get_templates_dir()` and have it use `temp_root`
-        // without mocking `dirs::config_dir()`.
-
-        // Let's test a slightly refactored idea: a function that takes a base path.
-        fn get_templates_dir_with_base(base_path: &PathBuf) -> Result<PathBuf, ConfigError> {
-            let mut path = base_path.clone();
-            path.push("templates");
-            if !path.exists() {
-                fs::create_dir_all(&path)
-                    .map_err(|e| ConfigError::DirectoryCreationError(path.clone(), e))?;
+        let store = Store::new(temp_root.path());
+        let flat = "[version]\nfilename_in_storage = \"version.txt\"\nsource_extension = \"txt\"\ncreated_at = 0\nupdated_at = 0\n";
+        fs::write(store.manifest_path(), flat).unwrap();
+
+        let manifest = store.load_manifest().unwrap();
+        assert_eq!(manifest.version, MANIFEST_VERSION);
+        assert_eq!(manifest.get_template("version").unwrap().filename_in_storage, "version.txt");
+        // Written back in the current format, with the flat file kept as a backup
+        let saved = fs::read_to_string(store.manifest_path()).unwrap();
+        assert!(saved.starts_with(&format!("version = {MANIFEST_VERSION}")), "{saved}");
+        assert_eq!(fs::read_to_string(store.manifest_backup_path()).unwrap(), flat);
+        assert!(store.load_manifest().unwrap().get_template("version").is_some());
+
+        fs::write(store.manifest_path(), "version = 99\n[settings]\ntheme = \"dark\"\n").unwrap();
+        assert!(matches!(store.load_manifest(), Err(ConfigError::ManifestTooNew { version: 99, .. })));
+    }
+
+    #[test]
+    fn test_local_store_shadows_global() {
+        let project = tempdir().unwrap();
+        let nested = project.path().join("src").join("deep");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(project.path().join(LOCAL_STORE_DIR).join(TEMPLATES_DIR_NAME)).unwrap();
+        let local_root = find_local_store(&nested).unwrap();
+        assert_eq!(local_root, project.path().join(LOCAL_STORE_DIR));
+
+        let global_root = tempdir().unwrap();
+        let stores = Stores {
+            local: Some(Store::new(local_root)),
+            global: Store::new(global_root.path()),
+        };
+        for (layer, names) in [(Layer::Local, ["io"].as_slice()), (Layer::Global, ["io", "main"].as_slice())] {
+            let store = stores.layers().into_iter().find(|(l, _)| *l == layer).unwrap().1;
+            let mut manifest = Manifest::new();
+            for name in names {
+                manifest.add_template(name.to_string(), TemplateEntry::new(format!("{name}.rs"), "rs".to_string()));
             }
-            Ok(path)
+            store.save_manifest(&manifest).unwrap();
         }
 
-        let result = get_templates_dir_with_base(&app_config);
-        assert!(result.is_ok());
-        let templates_path = result.unwrap();
-        assert_eq!(templates_path, templates_dir_expected);
-        assert!(templates_path.exists());
-        assert!(templates_path.is_dir());
+        let layers = stores.load().unwrap();
+        assert_eq!(find_template(&layers, "io").unwrap().0.layer, Layer::Local);
+        assert_eq!(find_template(&layers, "main").unwrap().0.layer, Layer::Global);
+        assert!(find_template(&layers, "missing").is_none());
+    }
+
+    #[test]
+    fn test_bare_tempo_dir_is_not_a_local_store() {
+        let project = tempdir().unwrap();
+        let nested = project.path().join("src");
+        fs::create_dir_all(nested.join(LOCAL_STORE_DIR)).unwrap();
+        assert_eq!(find_local_store(&nested), None);
 
-        // Clean up (temp_root does this automatically when it goes out of scope)
+        fs::create_dir(project.path().join(LOCAL_STORE_DIR)).unwrap();
+        fs::write(project.path().join(LOCAL_STORE_DIR).join(MANIFEST_FILENAME), "").unwrap();
+        assert_eq!(find_local_store(&nested), Some(project.path().join(LOCAL_STORE_DIR)));
     }
 
     #[test]
@@ -0,0 +1,224 @@
+use crate::manifest::TemplateEntry;
+use crate::template::{Value, Variables};
+use chrono::Local;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The names of the variables `builtin_variables` provides. A template cannot declare
+/// variables of these names.
+pub const BUILTIN_NAMES: &[&str] = &[
+    "file_name", "file_stem", "file_ext", "file_dir", "date", "time", "datetime", "year", "template", "language", "user",
+    "git_author", "git_email",
+];
+
+/// Builds the variables every template can use without passing `--set`:
+///  - `file_name`, `file_stem`, `file_ext`, `file_dir`: describe the destination file.
+///  - `date`, `time`, `datetime`, `year`: local time of the apply.
+///  - `template`: name of the template being applied.
+///  - `language`: `TemplateEntry::language`, falling back to the source extension.
+///  - `user`: login name from `USER` (or `USERNAME` on Windows).
+///  - `git_author`, `git_email`: `user.name`/`user.email` from the nearest git config.
+///
+/// Values that cannot be determined are left out, so using them fails like any
+/// other missing variable. Values given with `--set` take precedence over these.
+pub fn builtin_variables(template_name: &str, entry: &TemplateEntry, dest_path: &Path) -> Variables {
+    let mut vars = file_variables(dest_path);
+    let dest_dir = destination_dir(dest_path);
+
+    let now = Local::now();
+    vars.insert("date".into(), now.format("%Y-%m-%d").to_string().into());
+    vars.insert("time".into(), now.format("%H:%M:%S").to_string().into());
+    vars.insert("datetime".into(), now.format("%Y-%m-%d %H:%M:%S").to_string().into());
+    vars.insert("year".into(), now.format("%Y").to_string().into());
+
+    vars.insert("template".into(), template_name.into());
+    let language = entry
+        .language
+        .clone()
+        .unwrap_or_else(|| entry.source_extension.clone());
+    vars.insert("language".into(), language.into());
+
+    if let Ok(user) = env::var("USER").or_else(|_| env::var("USERNAME")) {
+        vars.insert("user".into(), user.into());
+    }
+
+    let git_user = git_user_config(&dest_dir);
+    if let Some(name) = git_user.name {
+        vars.insert("git_author".into(), name.into());
+    }
+    if let Some(email) = git_user.email {
+        vars.insert("git_email".into(), email.into());
+    }
+
+    vars
+}
+
+/// The built-in variables describing the destination file: `file_name`, `file_stem`,
+/// `file_ext` and `file_dir`.
+pub fn file_variables(dest_path: &Path) -> Variables {
+    let mut vars = Variables::new();
+
+    if let Some(name) = dest_path.file_name().and_then(|n| n.to_str()) {
+        vars.insert("file_name".into(), name.into());
+    }
+    if let Some(stem) = dest_path.file_stem().and_then(|s| s.to_str()) {
+        vars.insert("file_stem".into(), stem.into());
+    }
+    let ext = dest_path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    vars.insert("file_ext".into(), ext.into());
+    vars.insert("file_dir".into(), destination_dir(dest_path).display().to_string().into());
+
+    vars
+}
+
+/// Points the file built-ins in `vars` at `dest_path`, for templates writing several
+/// files. Values given with `--set` are kept.
+pub fn update_file_variables(vars: &mut Variables, dest_path: &Path, overrides: &[(String, String)]) {
+    for (key, value) in file_variables(dest_path) {
+        if !overrides.iter().any(|(name, _)| *name == key) {
+            vars.insert(key, value);
+        }
+    }
+}
+
+/// The built-in variables for applying `template_name` to `dest_path`, overridden by
+/// the `KEY=VALUE` pairs given with `--set`.
+pub fn template_variables(
+    template_name: &str,
+    entry: &TemplateEntry,
+    dest_path: &Path,
+    overrides: &[(String, String)],
+) -> Variables {
+    let mut vars = builtin_variables(template_name, entry, dest_path);
+    vars.extend(
+        overrides
+            .iter()
+            .map(|(key, value)| (key.clone(), Value::from(value.as_str()))),
+    );
+    vars
+}
+
+/// The directory a destination path will be written into, absolute when it can be resolved.
+fn destination_dir(dest_path: &Path) -> PathBuf {
+    let parent = match dest_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    // The directory may not exist yet; fall back to the path as given
+    parent.canonicalize().unwrap_or(parent)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct GitUser {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+/// Looks up `user.name` and `user.email`, first in the config of the git repository
+/// enclosing `start_dir`, then in the user's global git config.
+fn git_user_config(start_dir: &Path) -> GitUser {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    if let Some(git_dir) = find_git_dir(start_dir) {
+        candidates.push(git_dir.join("config"));
+    }
+    if let Some(home) = dirs::home_dir() {
+        candidates.push(home.join(".gitconfig"));
+    }
+    if let Some(config) = dirs::config_dir() {
+        candidates.push(config.join("git").join("config"));
+    }
+
+    let mut user = GitUser::default();
+    for path in candidates {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let found = parse_git_user(&content);
+        user.name = user.name.or(found.name);
+        user.email = user.email.or(found.email);
+        if user.name.is_some() && user.email.is_some() {
+            break;
+        }
+    }
+    user
+}
+
+/// Walks up from `start_dir` to the nearest `.git` directory.
+/// Also follows `.git` files (`gitdir: <path>`) used by worktrees and submodules.
+fn find_git_dir(start_dir: &Path) -> Option<PathBuf> {
+    // The destination directory may not exist yet, so walk the absolute path as given
+    let start = std::path::absolute(start_dir).ok()?;
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        if dot_git.is_dir() {
+            return Some(dot_git);
+        }
+        if dot_git.is_file() {
+            let content = fs::read_to_string(&dot_git).ok()?;
+            let target = content.trim().strip_prefix("gitdir:")?.trim();
+            return Some(dir.join(target));
+        }
+    }
+    None
+}
+
+/// Extracts `name` and `email` from the `[user]` section of a git config file.
+fn parse_git_user(content: &str) -> GitUser {
+    let mut user = GitUser::default();
+    let mut in_user_section = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_user_section = line.trim_matches(|c| c == '[' || c == ']').trim().eq_ignore_ascii_case("user");
+            continue;
+        }
+        if !in_user_section {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim().to_lowercase().as_str() {
+                "name" => user.name = Some(value),
+                "email" => user.email = Some(value),
+                _ => {}
+            }
+        }
+    }
+    user
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_git_user_reads_user_section_only() {
+        let config = "[core]\n\tname = not-me\n[user]\n\tname = \"Ada Lovelace\"\n\temail = ada@example.com\n[alias]\n\tco = checkout\n";
+        let user = parse_git_user(config);
+        assert_eq!(user.name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(user.email.as_deref(), Some("ada@example.com"));
+    }
+
+    #[test]
+    fn test_builtin_variables_describe_destination() {
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("solve_1234A.cpp");
+        let mut entry = TemplateEntry::new("cp.cpp".into(), "cpp".into());
+        entry.language = Some("C++".into());
+
+        let vars = builtin_variables("cp", &entry, &dest);
+        assert!(vars.keys().all(|name| BUILTIN_NAMES.contains(&name.as_str())));
+        assert_eq!(vars["file_name"].to_string(), "solve_1234A.cpp");
+        assert_eq!(vars["file_stem"].to_string(), "solve_1234A");
+        assert_eq!(vars["file_ext"].to_string(), "cpp");
+        assert_eq!(vars["template"].to_string(), "cp");
+        assert_eq!(vars["language"].to_string(), "C++");
+        assert_eq!(vars["date"].to_string().len(), "YYYY-MM-DD".len());
+    }
+}
@@ -0,0 +1,51 @@
+use colored::*;
+use similar::{ChangeTag, TextDiff};
+
+/// Lines of context shown around each change.
+const CONTEXT_LINES: usize = 3;
+
+/// Builds a colored unified diff from `old` to `new`, or `None` if they are equal.
+///
+/// Content that is not UTF-8 text is only reported as differing.
+pub fn unified(old: &[u8], new: &[u8], old_label: &str, new_label: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+        return Some(format!("Binary files {old_label} and {new_label} differ\n"));
+    };
+
+    let diff = TextDiff::from_lines(old, new);
+    let mut out = format!("{}\n{}\n", format!("--- {old_label}").bold(), format!("+++ {new_label}").bold());
+    for hunk in diff.unified_diff().context_radius(CONTEXT_LINES).iter_hunks() {
+        out.push_str(&format!("{}\n", hunk.header().to_string().cyan()));
+        for change in hunk.iter_changes() {
+            let line = change.to_string_lossy();
+            let line = line.trim_end_matches('\n');
+            let text = match change.tag() {
+                ChangeTag::Delete => format!("-{line}").red().to_string(),
+                ChangeTag::Insert => format!("+{line}").green().to_string(),
+                ChangeTag::Equal => format!(" {line}"),
+            };
+            out.push_str(&text);
+            out.push('\n');
+            if change.missing_newline() {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unified_diff_marks_changed_lines() {
+        colored::control::set_override(false);
+        let diff = unified(b"a\nb\nc\n", b"a\nB\nc\n", "x.rs", "x.rs (after apply)").unwrap();
+        assert_eq!(diff, "--- x.rs\n+++ x.rs (after apply)\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+        assert_eq!(unified(b"same", b"same", "a", "b"), None);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::config; // For config::ConfigError
+use crate::template::{self, MissingVariable, TemplateError};
 use std::io;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -10,6 +11,13 @@ pub enum AppError {
     #[error("Configuration error: {0}")]
     Config(#[from] config::ConfigError), // Allows easy conversion from config::ConfigError
 
+    #[error("Timed out after {waited}s waiting for the template store, which is locked by {holder} (lock file {path:?}).")]
+    StoreLocked {
+        path: PathBuf,
+        holder: String,
+        waited: f64,
+    },
+
     // --- I/O Errors ---
     #[error("I/O error: {0}")]
     Io(#[from] io::Error), // General I/O errors
@@ -41,15 +49,24 @@ pub enum AppError {
     #[error("Source file does not exist: {0:?}")]
     SourceFileDoesNotExist(PathBuf),
 
-    #[error("Source path is not a file: {0:?}")]
+    #[error("Source path is not a file or directory: {0:?}")]
     SourcePathIsNotAFile(PathBuf),
 
+    #[error("Directory {0:?} has no files left to store after applying the ignore rules.")]
+    EmptyDirectoryTemplate(PathBuf),
+
+    #[error("Invalid front matter in {path:?}: {reason}")]
+    InvalidFrontMatter { path: PathBuf, reason: String },
+
     #[error("Template name '{0}' is invalid: {1}")]
     TemplateNameInvalid(String, String), // name, reason
 
     #[error("Template '{0}' already exists. Use --force to overwrite.")]
     TemplateAlreadyExists(String), // name
 
+    #[error("No project store (.tempo/) found in {0:?} or its parents. Create one with 'tempo add --local'.")]
+    NoProjectStore(PathBuf),
+
     // 'list' command related (could also be generic)
     #[error("Templates directory not found at: {0:?}")] // Could be part of Config if critical
     TemplatesDirNotFound(PathBuf),
@@ -69,9 +86,81 @@ pub enum AppError {
     )]
     DestinationFileExists(PathBuf),
 
+    #[error("Template '{name}' could not be rendered: {source}")]
+    TemplateRender {
+        name: String,
+        #[source]
+        source: TemplateError,
+    },
+
+    #[error("Template '{name}' includes itself: {}", .chain.join(" -> "))]
+    IncludeCycle {
+        name: String,
+        chain: Vec<String>, // from the applied template to the one included twice
+    },
+
+    #[error("Template '{name}' needs values for: {}. Provide them with --set <KEY=VALUE>.", template::join_missing(.missing))]
+    MissingTemplateVariables {
+        name: String,
+        missing: Vec<MissingVariable>,
+    },
+
+    #[error("Invalid value '{value}' for variable '{variable}' of template '{name}': {reason}")]
+    InvalidVariableValue {
+        name: String,
+        variable: String,
+        value: String,
+        reason: String,
+    },
+
+    #[error("Template '{name}' declares variable '{variable}', which is built in. Rename it in its front matter or manifest entry.")]
+    BuiltinVariableDeclared { name: String, variable: String },
+
     #[error("Cannot {action} to destination '{dest:?}' because it's a directory.")]
     DestinationIsDirectory { action: String, dest: PathBuf },
 
+    #[error("Invalid anchor regex '{pattern}': {reason}")]
+    InvalidAnchorPattern { pattern: String, reason: String },
+
+    #[error("No line of {dest:?} matches {anchor}.")]
+    AnchorNotFound { dest: PathBuf, anchor: String },
+
+    #[error("{dest:?} does not exist, so there is no line to match {anchor}.")]
+    AnchorDestinationMissing { dest: PathBuf, anchor: String },
+
+    #[error("{anchor} matches more than one line of {dest:?} (lines {}). Use a more specific anchor.", join_numbers(.lines))]
+    AnchorAmbiguous {
+        dest: PathBuf,
+        anchor: String,
+        lines: Vec<usize>,
+    },
+
+    #[error("No managed block of template '{name}' found in {dest:?}.")]
+    ManagedBlockNotFound { name: String, dest: PathBuf },
+
+    #[error("Template '{name}' is a single file, but {dest:?} is a directory. Give the file to remove its managed block from.")]
+    ManagedBlockInDirectory { name: String, dest: PathBuf },
+
+    #[error("The managed block of template '{name}' in {dest:?} {reason}.")]
+    ManagedBlockInvalid {
+        name: String,
+        dest: PathBuf,
+        reason: String,
+    },
+
+    #[error("Template '{name}' holds a directory, but destination '{dest:?}' is not a directory.")]
+    DestinationIsNotADirectory { name: String, dest: PathBuf },
+
+    #[error("Template '{name}' produced an invalid path '{path}': {reason}")]
+    InvalidRenderedPath {
+        name: String,
+        path: String,
+        reason: String,
+    },
+
+    #[error("Cannot {action} template '{name}' because it holds a directory. Use 'tempo path {name}' to find its files.")]
+    DirectoryTemplateUnsupported { action: String, name: String },
+
     #[error("Confirmation required for '{action}' on template '{template_name}', but running in quiet mode. Use --force.")]
     ConfirmationNeededInQuietMode {
         action: String,
@@ -85,7 +174,82 @@ pub enum AppError {
         source: std::io::Error,
     },
 
+    // 'undo' command related
+    #[error("The apply journal at {path:?} is corrupt: {source}")]
+    JournalParse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("{} changed since the apply. Use --discard-changes to undo anyway and lose those changes.", join_paths(.0))]
+    UndoWouldLoseChanges(Vec<PathBuf>),
+
+    // 'history', 'restore' and 'diff' related
+    #[error("Template '{name}' has no revision {rev}; it has {count}. See 'tempo history {name}'.")]
+    RevisionNotFound { name: String, rev: u32, count: usize },
+
+    #[error("A revision of template '{name}' is missing from the store: {path:?} does not exist.")]
+    RevisionObjectMissing { name: String, path: PathBuf },
+
+    // 'store init --git' and 'sync' related
+    #[error("git {command} failed: {message}")]
+    Git { command: String, message: String },
+
+    #[error("The template store at {0:?} is not a git repository. Run 'tempo store init --git' first.")]
+    NotAGitStore(PathBuf),
+
+    #[error("The template store at {0:?} has no remote to sync with. Add one with 'tempo store init --git --remote <URL>'.")]
+    NoSyncRemote(PathBuf),
+
+    #[error("Sync stopped: {} changed both here and on the remote. Nothing was changed; resolve it with git in the store directory, then sync again.", .0.join(", "))]
+    SyncConflict(Vec<String>),
+
+    // 'export' and 'import' related
+    #[error("Bundle {0:?} already exists. Use --force to overwrite.")]
+    BundleExists(PathBuf),
+
+    #[error("No templates match the given names or tags.")]
+    NothingToExport,
+
+    #[error("{path:?} is not a valid template bundle: {reason}")]
+    InvalidBundle { path: PathBuf, reason: String },
+
+    // 'doctor' command related
+    #[error("Found {count} problem(s) in the template store.{hint}")]
+    DoctorFoundProblems { count: usize, hint: String },
+
     // General / Other
     #[error("An unexpected error occurred: {0}")]
     Unexpected(String),
 }
+
+impl AppError {
+    /// Attaches the template name to a rendering error.
+    pub fn from_template(name: &str, err: TemplateError) -> Self {
+        match err {
+            TemplateError::MissingVariables(missing) => AppError::MissingTemplateVariables {
+                name: name.to_string(),
+                missing,
+            },
+            TemplateError::IncludeCycle(chain) => AppError::IncludeCycle {
+                name: name.to_string(),
+                chain,
+            },
+            source => AppError::TemplateRender {
+                name: name.to_string(),
+                source,
+            },
+        }
+    }
+}
+
+/// Joins paths for error messages, e.g. `"src/a.rs", "src/b.rs"`.
+pub fn join_paths(paths: &[PathBuf]) -> String {
+    paths.iter().map(|path| format!("{path:?}")).collect::<Vec<_>>().join(", ")
+}
+
+/// Joins line numbers for error messages, e.g. `3, 10`.
+pub fn join_numbers(numbers: &[usize]) -> String {
+    numbers.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")
+}
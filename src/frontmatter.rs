@@ -0,0 +1,127 @@
+use crate::context;
+use crate::manifest::{TemplateEntry, WriteStrategy};
+use crate::schema::VariableSpec;
+use serde::Deserialize;
+
+const DELIMITER: &str = "+++";
+
+/// Metadata declared in a `+++`-delimited TOML block at the top of a template file:
+///
+/// ```text
+/// +++
+/// description = "Competitive programming starter"
+/// tags = ["cp", "cpp"]
+/// language = "cpp"
+/// strategy = "overwrite"
+///
+/// [[variables]]
+/// name = "problem"
+/// required = true
+/// +++
+/// // Problem {{ problem }}
+/// ```
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FrontMatter {
+    pub description: Option<String>,
+
+    #[serde(default)]
+    pub tags: Vec<String>,
+
+    pub language: Option<String>,
+
+    /// Write strategy `apply` uses when the destination exists and no `-o`/`-a`/`-p` is given.
+    pub strategy: Option<WriteStrategy>,
+
+    #[serde(default)]
+    pub variables: Vec<VariableSpec>,
+}
+
+impl FrontMatter {
+    /// Copies the declared metadata onto a manifest entry.
+    pub fn apply_to(&self, entry: &mut TemplateEntry) {
+        entry.description = self.description.clone();
+        entry.tags = self.tags.clone();
+        entry.language = self.language.clone();
+        entry.default_strategy = self.strategy.clone();
+        entry.variables = self.variables.clone();
+    }
+}
+
+/// Splits a template file into its front matter (if it has one) and its body.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the block is never closed, is not valid TOML,
+/// or declares a variable named like a built-in one, whose value it could not check.
+pub fn split(content: &str) -> Result<(Option<FrontMatter>, &str), String> {
+    let content_start = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let Some(after_open) = strip_delimiter_line(content_start) else {
+        return Ok((None, content));
+    };
+
+    let mut offset = 0;
+    for line in after_open.split_inclusive('\n') {
+        if line.trim_end() == DELIMITER {
+            let header = &after_open[..offset];
+            let body = &after_open[offset + line.len()..];
+            let front_matter: FrontMatter = toml::from_str(header).map_err(|e| e.message().to_string())?;
+            if let Some(spec) = front_matter
+                .variables
+                .iter()
+                .find(|spec| context::BUILTIN_NAMES.contains(&spec.name.as_str()))
+            {
+                return Err(format!("variable '{}' is built in and cannot be declared; use another name", spec.name));
+            }
+            return Ok((Some(front_matter), body));
+        }
+        offset += line.len();
+    }
+
+    Err(format!("the '{DELIMITER}' block at the top of the file is never closed"))
+}
+
+/// Returns the rest of `content` if its first line is the opening delimiter.
+fn strip_delimiter_line(content: &str) -> Option<&str> {
+    let first_line_end = content.find('\n').map_or(content.len(), |nl| nl + 1);
+    if content[..first_line_end].trim_end() == DELIMITER {
+        Some(&content[first_line_end..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_parses_header_and_returns_body() {
+        let content = "+++\ndescription = \"Fast IO\"\ntags = [\"cp\"]\nstrategy = \"prepend\"\n\n[[variables]]\nname = \"n\"\ntype = \"int\"\n+++\nint main() {}\n";
+        let (front_matter, body) = split(content).unwrap();
+        let front_matter = front_matter.unwrap();
+        assert_eq!(front_matter.description.as_deref(), Some("Fast IO"));
+        assert_eq!(front_matter.tags, vec!["cp"]);
+        assert_eq!(front_matter.strategy, Some(WriteStrategy::Prepend));
+        assert_eq!(front_matter.variables[0].name, "n");
+        assert_eq!(body, "int main() {}\n");
+    }
+
+    #[test]
+    fn test_split_without_header_keeps_content() {
+        let content = "x = 1\n+++\n";
+        assert_eq!(split(content).unwrap(), (None, content));
+    }
+
+    #[test]
+    fn test_split_rejects_unclosed_or_unknown_keys() {
+        assert!(split("+++\ndescription = \"x\"\n").is_err());
+        assert!(split("+++\ndescriptoin = \"x\"\n+++\n").is_err());
+    }
+
+    #[test]
+    fn test_split_rejects_declaring_a_builtin_variable() {
+        let error = split("+++\n[[variables]]\nname = \"date\"\npattern = \"^Q[1-4]$\"\n+++\n{{ date }}").unwrap_err();
+        assert!(error.contains("'date' is built in"));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::atomic;
+use crate::error::AppError;
+use crate::manifest::{self, Manifest};
+use crate::output::OutputConfig;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Kept out of the repository: they only make sense on the machine that wrote them.
+const GITIGNORE: &str = "\
+# Local to this machine
+tempo.lock
+journal/
+manifest.toml.bak
+";
+
+/// Makes git report every concurrent change of the manifest as a conflict, which
+/// `tempo sync` then merges by template name rather than line by line.
+const GITATTRIBUTES: &str = "\
+# Merged by tempo sync, template by template
+manifest.toml -merge
+";
+
+const MANIFEST: &str = "manifest.toml";
+
+/// Used for commits when git has no `user.name`/`user.email` configured.
+const FALLBACK_IDENTITY: [&str; 4] = ["-c", "user.name=tempo", "-c", "user.email=tempo@localhost"];
+
+/// Whether the store at `root` is a git repository of its own.
+pub fn is_repo(root: &Path) -> bool {
+    root.join(".git").exists()
+}
+
+/// Turns the store at `root` into a git repository and commits its current content.
+/// Running it again keeps the repository and only updates the remote.
+///
+/// Returns whether the repository was created.
+pub fn init(root: &Path, remote: Option<&str>) -> Result<bool, AppError> {
+    let created = !is_repo(root);
+    if created {
+        git(root, &["init", "--quiet", "--initial-branch=main"])?;
+    }
+    for (name, content) in [(".gitignore", GITIGNORE), (".gitattributes", GITATTRIBUTES)] {
+        if !root.join(name).exists() {
+            atomic::write(&root.join(name), content.as_bytes())?;
+        }
+    }
+    if let Some(url) = remote {
+        if git(root, &["remote"])?.lines().any(|name| name == "origin") {
+            git(root, &["remote", "set-url", "origin", url])?;
+        } else {
+            git(root, &["remote", "add", "origin", url])?;
+        }
+    }
+    commit_all(root, "Initialize tempo store")?;
+    Ok(created)
+}
+
+/// Commits every change in the store, if it is a repository and anything changed.
+///
+/// Returns whether a commit was made.
+pub fn commit_all(root: &Path, message: &str) -> Result<bool, AppError> {
+    if !is_repo(root) {
+        return Ok(false);
+    }
+    git(root, &["add", "--all"])?;
+    if git(root, &["status", "--porcelain"])?.is_empty() {
+        return Ok(false);
+    }
+    commit(root, &["commit", "--quiet", "-m", message])?;
+    Ok(true)
+}
+
+/// Commits the changes a command made to the store, warning instead of failing: the
+/// change itself is already done, and `tempo sync` commits whatever is left over.
+pub fn autocommit(root: &Path, message: &str, output: &OutputConfig) {
+    match commit_all(root, message) {
+        Ok(true) => output.verbose(format!("\t\t[VERBOSE] Committed to the store repository: {message}")),
+        Ok(false) => {}
+        Err(e) => output.warn(format!("\t\tWarning: Could not commit the change to the store repository: {e}")),
+    }
+}
+
+/// What `sync` did.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncReport {
+    /// Commits fetched from the remote and applied below the local ones.
+    pub pulled: usize,
+    /// Local commits sent to the remote.
+    pub pushed: usize,
+    /// Whether concurrent changes of `manifest.toml` were merged by template name.
+    pub merged_manifest: bool,
+}
+
+/// Pulls the remote's changes, replays the local commits on top of them and pushes.
+///
+/// Both sides changing the manifest is resolved with `manifest::merge`; both sides
+/// changing the same template file stops the sync with `AppError::SyncConflict`, leaving
+/// the store as it was before.
+pub fn sync(root: &Path) -> Result<SyncReport, AppError> {
+    if !is_repo(root) {
+        return Err(AppError::NotAGitStore(root.to_path_buf()));
+    }
+    let remote = remote_name(root)?.ok_or_else(|| AppError::NoSyncRemote(root.to_path_buf()))?;
+    commit_all(root, "Update templates")?;
+    let branch = git(root, &["symbolic-ref", "--short", "HEAD"])?;
+    let upstream = format!("{remote}/{branch}");
+
+    let mut report = SyncReport::default();
+    git(root, &["fetch", "--quiet", &remote])?;
+    let has_upstream = run(root, &["rev-parse", "--verify", "--quiet", &format!("refs/remotes/{upstream}")])?.status.success();
+    if has_upstream {
+        report.pulled = count(root, &format!("HEAD..{upstream}"))?;
+        if report.pulled > 0 {
+            report.merged_manifest = rebase(root, &upstream)?;
+        }
+        report.pushed = count(root, &format!("{upstream}..HEAD"))?;
+    } else {
+        report.pushed = count(root, "HEAD")?;
+    }
+    if report.pushed > 0 {
+        git(root, &["push", "--quiet", "--set-upstream", &remote, &branch])?;
+    }
+    Ok(report)
+}
+
+/// Replays the local commits onto `upstream`, merging manifest conflicts by name.
+fn rebase(root: &Path, upstream: &str) -> Result<bool, AppError> {
+    let mut merged_manifest = false;
+    let mut result = commit(root, &["rebase", "--quiet", upstream]);
+    while let Err(error) = result {
+        let conflicted = git(root, &["diff", "--name-only", "--diff-filter=U"])?;
+        let conflicted: Vec<String> = conflicted.lines().map(str::to_string).collect();
+        if conflicted != [MANIFEST] {
+            let _ = run(root, &["rebase", "--abort"]);
+            let unmergeable: Vec<String> = conflicted.into_iter().filter(|path| path != MANIFEST).collect();
+            return Err(if unmergeable.is_empty() { error } else { AppError::SyncConflict(unmergeable) });
+        }
+
+        // Stage 1 is the common ancestor, 2 the upstream side and 3 the commit being replayed
+        let stage = |n: u8| -> Result<Manifest, AppError> {
+            let content = run(root, &["show", &format!(":{n}:{MANIFEST}")])?;
+            let content = String::from_utf8_lossy(&content.stdout);
+            manifest::parse(&content)
+                .map(|(manifest, _)| manifest)
+                .map_err(|_| AppError::SyncConflict(vec![MANIFEST.to_string()]))
+        };
+        let merged = match (stage(1), stage(2), stage(3)) {
+            (base, Ok(ours), Ok(theirs)) => manifest::merge(&base.unwrap_or_default(), &ours, &theirs),
+            _ => {
+                let _ = run(root, &["rebase", "--abort"]);
+                return Err(AppError::SyncConflict(conflicted));
+            }
+        };
+        let content = toml::to_string_pretty(&merged).map_err(|e| AppError::Unexpected(e.to_string()))?;
+        fs::write(root.join(MANIFEST), content)?;
+        git(root, &["add", MANIFEST])?;
+        merged_manifest = true;
+        // The commit may have nothing left to add, e.g. the same template added on both sides
+        let empty = run(root, &["diff", "--cached", "--quiet"])?.status.success();
+        result = commit(root, &["rebase", if empty { "--skip" } else { "--continue" }]);
+    }
+    Ok(merged_manifest)
+}
+
+/// The remote to sync with: `origin`, or the only one there is.
+fn remote_name(root: &Path) -> Result<Option<String>, AppError> {
+    let remotes = git(root, &["remote"])?;
+    let remotes: Vec<&str> = remotes.lines().collect();
+    Ok(match remotes.as_slice() {
+        [] => None,
+        [only] => Some(only.to_string()),
+        _ if remotes.contains(&"origin") => Some("origin".to_string()),
+        [first, ..] => Some(first.to_string()),
+    })
+}
+
+fn count(root: &Path, range: &str) -> Result<usize, AppError> {
+    Ok(git(root, &["rev-list", "--count", range])?.parse().unwrap_or(0))
+}
+
+/// Runs a git command that creates commits, with a fallback identity if none is configured.
+fn commit(root: &Path, args: &[&str]) -> Result<String, AppError> {
+    let has_identity = run(root, &["config", "user.email"])?.status.success();
+    let args: Vec<&str> = if has_identity { args.to_vec() } else { [&FALLBACK_IDENTITY[..], args].concat() };
+    git(root, &args)
+}
+
+/// Runs git in `root`, returning its trimmed standard output.
+fn git(root: &Path, args: &[&str]) -> Result<String, AppError> {
+    let output = run(root, args)?;
+    if !output.status.success() {
+        return Err(AppError::Git {
+            command: args.join(" "),
+            message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        });
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn run(root: &Path, args: &[&str]) -> Result<Output, AppError> {
+    Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        // Never open an editor, e.g. for the message of `rebase --continue`
+        .env("GIT_EDITOR", "true")
+        .output()
+        .map_err(|e| AppError::Git {
+            command: args.join(" "),
+            message: format!("could not run git: {e}"),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Store;
+    use crate::manifest::TemplateEntry;
+
+    fn add(store: &Store, name: &str) {
+        fs::write(store.templates_dir().unwrap().join(format!("{name}.rs")), name).unwrap();
+        let mut manifest = store.load_manifest().unwrap();
+        manifest.add_template(name.to_string(), TemplateEntry::new(format!("{name}.rs"), "rs".to_string()));
+        store.save_manifest(&manifest).unwrap();
+        commit_all(store.root_path(), &format!("tempo add {name}")).unwrap();
+    }
+
+    #[test]
+    fn test_sync_merges_templates_added_on_two_machines() {
+        let dir = tempfile::tempdir().unwrap();
+        let remote = dir.path().join("remote.git");
+        fs::create_dir(&remote).unwrap();
+        git(&remote, &["init", "--quiet", "--bare", "--initial-branch=main"]).unwrap();
+        let url = remote.to_str().unwrap();
+        let (laptop, desktop) = (Store::new(dir.path().join("laptop")), Store::new(dir.path().join("desktop")));
+        for store in [&laptop, &desktop] {
+            assert!(init(&store.root().unwrap(), Some(url)).unwrap());
+        }
+
+        add(&laptop, "io");
+        assert_eq!(sync(laptop.root_path()).unwrap().pushed, 2);
+        add(&desktop, "segtree");
+        add(&laptop, "graph");
+        sync(laptop.root_path()).unwrap();
+
+        let report = sync(desktop.root_path()).unwrap();
+        assert!(report.merged_manifest);
+        let mut names: Vec<String> = desktop.load_manifest().unwrap().templates.into_keys().collect();
+        names.sort();
+        assert_eq!(names, ["graph", "io", "segtree"]);
+        assert!(desktop.templates_dir().unwrap().join("graph.rs").exists());
+
+        assert_eq!(sync(laptop.root_path()).unwrap().pulled, 1);
+        assert_eq!(laptop.load_manifest().unwrap().templates.len(), 3);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::atomic;
+use crate::config::Store;
+use crate::error::AppError;
+use crate::manifest::{Revision, TemplateEntry};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Keeps the stored content of `entry` as a revision, before it is replaced.
+///
+/// Returns `None` when there is nothing to keep: the template holds a directory, its
+/// file is gone, or the content is already its latest revision.
+pub fn snapshot(store: &Store, entry: &TemplateEntry, templates_dir: &Path) -> Result<Option<Revision>, AppError> {
+    if entry.is_directory() {
+        return Ok(None);
+    }
+    let content = match fs::read(entry.stored_path(templates_dir)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(AppError::Io(e)),
+    };
+    keep(store, entry, &content)
+}
+
+/// Keeps `content`, which `entry` held until now, as a revision. Returns `None` if it is
+/// already the latest one.
+pub fn keep(store: &Store, entry: &TemplateEntry, content: &[u8]) -> Result<Option<Revision>, AppError> {
+    let object = save_object(store, content)?;
+    if entry
+        .history
+        .last()
+        .is_some_and(|last| last.object == object && last.filename_in_storage == entry.filename_in_storage)
+    {
+        return Ok(None);
+    }
+    Ok(Some(Revision {
+        object,
+        filename_in_storage: entry.filename_in_storage.clone(),
+        source_extension: entry.source_extension.clone(),
+        saved_at: entry.updated_at,
+    }))
+}
+
+/// Stores `content` in the object area under its SHA-256, returning the hash. Identical
+/// contents share one object.
+pub fn save_object(store: &Store, content: &[u8]) -> Result<String, AppError> {
+    let object = Sha256::digest(content).iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+    let path = object_path(store, &object)?;
+    if !path.exists() {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        atomic::write(&path, content)?;
+    }
+    Ok(object)
+}
+
+/// Reads the content of a revision of template `name`.
+///
+/// # Errors
+///
+/// Returns `AppError::RevisionObjectMissing` if the object was deleted from the store.
+pub fn read_object(store: &Store, name: &str, revision: &Revision) -> Result<Vec<u8>, AppError> {
+    let path = object_path(store, &revision.object)?;
+    fs::read(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => AppError::RevisionObjectMissing { name: name.to_string(), path },
+        _ => AppError::Io(e),
+    })
+}
+
+/// Finds revision `number` (1 is the oldest) of template `name`.
+pub fn revision<'a>(entry: &'a TemplateEntry, name: &str, number: u32) -> Result<&'a Revision, AppError> {
+    (number as usize)
+        .checked_sub(1)
+        .and_then(|index| entry.history.get(index))
+        .ok_or_else(|| AppError::RevisionNotFound {
+            name: name.to_string(),
+            rev: number,
+            count: entry.history.len(),
+        })
+}
+
+/// `objects/3f/a9c2...`, split like git's, so no directory grows too large.
+fn object_path(store: &Store, object: &str) -> Result<PathBuf, AppError> {
+    let (prefix, rest) = object.split_at(2.min(object.len()));
+    Ok(store.objects_dir()?.join(prefix).join(rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_keeps_each_distinct_content_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::new(dir.path());
+        let templates_dir = store.templates_dir().unwrap();
+        fs::write(templates_dir.join("io.rs"), "v1").unwrap();
+        let mut entry = TemplateEntry::new("io.rs".to_string(), "rs".to_string());
+
+        let first = snapshot(&store, &entry, &templates_dir).unwrap().unwrap();
+        assert_eq!(read_object(&store, "io", &first).unwrap(), b"v1");
+        entry.history.push(first);
+        // The same content again is not a new revision
+        assert!(snapshot(&store, &entry, &templates_dir).unwrap().is_none());
+
+        fs::write(templates_dir.join("io.rs"), "v2").unwrap();
+        entry.history.push(snapshot(&store, &entry, &templates_dir).unwrap().unwrap());
+        assert_eq!(read_object(&store, "io", revision(&entry, "io", 2).unwrap()).unwrap(), b"v2");
+        assert!(matches!(revision(&entry, "io", 3), Err(AppError::RevisionNotFound { count: 2, .. })));
+        assert!(revision(&entry, "io", 0).is_err());
+    }
+}
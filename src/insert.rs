@@ -0,0 +1,137 @@
+use regex::Regex;
+
+/// Prefix of the comment lines `--at-marker <name>` looks for, e.g. `// tempo:insert helpers`.
+pub const MARKER_PREFIX: &str = "tempo:insert";
+
+/// Where to insert content into an existing file.
+#[derive(Debug)]
+pub enum Anchor<'a> {
+    /// After the line matching the pattern.
+    After(&'a str),
+    /// Before the line matching the pattern.
+    Before(&'a str),
+    /// Right above the `tempo:insert <name>` marker line, with the marker's indentation.
+    /// The marker stays in place, so later inserts come after earlier ones.
+    Marker(&'a str),
+}
+
+impl Anchor<'_> {
+    /// How the anchor was given on the command line, for error messages.
+    pub fn describe(&self) -> String {
+        match self {
+            Anchor::After(pattern) => format!("--after '{pattern}'"),
+            Anchor::Before(pattern) => format!("--before '{pattern}'"),
+            Anchor::Marker(name) => format!("marker '{MARKER_PREFIX} {name}'"),
+        }
+    }
+}
+
+/// Why content could not be inserted.
+#[derive(Debug, PartialEq)]
+pub enum InsertError {
+    InvalidPattern { pattern: String, reason: String },
+    NotFound,
+    /// 1-based numbers of all the matching lines.
+    Ambiguous(Vec<usize>),
+}
+
+/// Inserts `content` into `existing` at the single line matching `anchor`.
+///
+/// `content` is inserted as whole lines: a missing final newline is added.
+pub fn insert(existing: &str, content: &str, anchor: &Anchor<'_>) -> Result<String, InsertError> {
+    let matcher: Box<dyn Fn(&str) -> bool> = match anchor {
+        Anchor::After(pattern) | Anchor::Before(pattern) => {
+            let regex = Regex::new(pattern).map_err(|e| InsertError::InvalidPattern {
+                pattern: pattern.to_string(),
+                reason: e.to_string(),
+            })?;
+            Box::new(move |line| regex.is_match(line))
+        }
+        Anchor::Marker(name) => Box::new(move |line| is_marker(line, name)),
+    };
+
+    let lines: Vec<&str> = existing.split_inclusive('\n').collect();
+    let matches: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| matcher(line.trim_end_matches(['\n', '\r'])))
+        .map(|(index, _)| index)
+        .collect();
+    let index = match matches.as_slice() {
+        [] => return Err(InsertError::NotFound),
+        [index] => *index,
+        _ => return Err(InsertError::Ambiguous(matches.iter().map(|index| index + 1).collect())),
+    };
+
+    let mut content = content.to_string();
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    let mut result = String::with_capacity(existing.len() + content.len() + 1);
+    for (i, line) in lines.iter().enumerate() {
+        if i == index {
+            match anchor {
+                Anchor::After(_) => {
+                    result.push_str(line);
+                    if !line.ends_with('\n') {
+                        result.push('\n');
+                    }
+                    result.push_str(&content);
+                    continue;
+                }
+                Anchor::Before(_) => result.push_str(&content),
+                Anchor::Marker(_) => {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    for content_line in content.split_inclusive('\n') {
+                        if !content_line.trim().is_empty() {
+                            result.push_str(indent);
+                        }
+                        result.push_str(content_line);
+                    }
+                }
+            }
+        }
+        result.push_str(line);
+    }
+    Ok(result)
+}
+
+/// Whether `line` holds `tempo:insert <name>`, inside whatever comment syntax.
+fn is_marker(line: &str, name: &str) -> bool {
+    line.match_indices(MARKER_PREFIX).any(|(at, _)| {
+        let rest = &line[at + MARKER_PREFIX.len()..];
+        rest.starts_with(char::is_whitespace)
+            && rest.split_whitespace().next() == Some(name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_at_marker_keeps_marker_and_indentation() {
+        let existing = "impl S {\n    // tempo:insert helpers\n}\n";
+        let once = insert(existing, "fn a() {}", &Anchor::Marker("helpers")).unwrap();
+        let twice = insert(&once, "fn b() {}\n", &Anchor::Marker("helpers")).unwrap();
+        assert_eq!(twice, "impl S {\n    fn a() {}\n    fn b() {}\n    // tempo:insert helpers\n}\n");
+
+        assert_eq!(insert(existing, "x", &Anchor::Marker("help")), Err(InsertError::NotFound));
+    }
+
+    #[test]
+    fn test_insert_after_and_before_regex() {
+        let existing = "use a;\nuse b;\n\nfn main() {}";
+        assert_eq!(
+            insert(existing, "fn x() {}", &Anchor::Before(r"^fn main")).unwrap(),
+            "use a;\nuse b;\n\nfn x() {}\nfn main() {}"
+        );
+        assert_eq!(
+            insert(existing, "use c;", &Anchor::After(r"^use b;")).unwrap(),
+            "use a;\nuse b;\nuse c;\n\nfn main() {}"
+        );
+        assert_eq!(insert(existing, "x", &Anchor::After("^use")), Err(InsertError::Ambiguous(vec![1, 2])));
+        assert!(matches!(insert(existing, "x", &Anchor::After("(")), Err(InsertError::InvalidPattern { .. })));
+    }
+}
@@ -0,0 +1,337 @@
+use crate::atomic;
+use crate::config::Store;
+use crate::error::AppError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const JOURNAL_FILENAME: &str = "journal.toml";
+
+/// How many applies are kept; older entries and their backups are dropped.
+const MAX_ENTRIES: usize = 50;
+
+/// The record of recent applies, with backups of the files they changed, so they can be undone.
+///
+/// Stored as `journal/journal.toml`; the backups of an entry live in `journal/<id>/`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Journal {
+    /// Oldest first.
+    #[serde(default)]
+    pub entries: Vec<JournalEntry>,
+    #[serde(skip)]
+    dir: PathBuf,
+}
+
+/// One `tempo apply` that changed files.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalEntry {
+    pub id: String,
+    pub template: String,
+    /// The write strategy asked for, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub applied_at: DateTime<Utc>,
+    /// Set until the apply has written its files; still set if it was cut short.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pending: bool,
+    pub files: Vec<JournalFile>,
+}
+
+/// A file changed by an apply.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JournalFile {
+    /// Absolute path of the destination file.
+    pub path: PathBuf,
+    /// What the apply did to it, e.g. `append` or `create`.
+    pub action: String,
+    /// Name of the snapshot in the entry's directory; `None` if the apply created the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    /// SHA-256 of the content the apply writes, to tell whether the file changed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub written: Option<String>,
+}
+
+/// A file written by an apply, with its content before and after.
+pub struct Change<'a> {
+    pub path: &'a Path,
+    pub action: &'a str,
+    pub before: Option<&'a [u8]>,
+    pub after: &'a [u8],
+}
+
+impl Journal {
+    /// Loads the store's journal, or an empty one if there is none yet.
+    pub fn load(store: &Store) -> Result<Self, AppError> {
+        Self::load_from(store.journal_dir()?)
+    }
+
+    fn load_from(dir: PathBuf) -> Result<Self, AppError> {
+        let path = dir.join(JOURNAL_FILENAME);
+        let mut journal: Journal = match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).map_err(|e| AppError::JournalParse { path, source: e })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Journal::default(),
+            Err(e) => return Err(AppError::Io(e)),
+        };
+        journal.dir = dir;
+        Ok(journal)
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        let content = toml::to_string_pretty(self).map_err(|e| AppError::Unexpected(e.to_string()))?;
+        atomic::write(&self.dir.join(JOURNAL_FILENAME), content.as_bytes())?;
+        Ok(())
+    }
+
+    /// Records an apply before any of its changes is written: keeps the previous content
+    /// of every file it is about to change and drops the oldest entries beyond the limit.
+    ///
+    /// The entry is pending until `complete` is called, so an apply cut short, even by a
+    /// crash, can still be undone. Returns the id of the entry.
+    pub fn begin(&mut self, template: &str, strategy: Option<String>, changes: &[Change<'_>]) -> Result<String, AppError> {
+        let applied_at = Utc::now();
+        let mut id = applied_at.format("%Y%m%d-%H%M%S-%6f").to_string();
+        while self.dir.join(&id).exists() {
+            id.push('x');
+        }
+        let entry_dir = self.dir.join(&id);
+        fs::create_dir_all(&entry_dir)?;
+
+        let mut files = Vec::with_capacity(changes.len());
+        for (index, change) in changes.iter().enumerate() {
+            let backup = match change.before {
+                Some(content) => {
+                    let name = index.to_string();
+                    atomic::write(&entry_dir.join(&name), content)?;
+                    Some(name)
+                }
+                None => None,
+            };
+            files.push(JournalFile {
+                path: std::path::absolute(change.path)?,
+                action: change.action.to_string(),
+                backup,
+                written: Some(digest(change.after)),
+            });
+        }
+
+        self.entries.push(JournalEntry {
+            id: id.clone(),
+            template: template.to_string(),
+            strategy,
+            applied_at,
+            pending: true,
+            files,
+        });
+        while self.entries.len() > MAX_ENTRIES {
+            let dropped = self.entries.remove(0);
+            self.remove_backups(&dropped)?;
+        }
+        self.save()?;
+        Ok(id)
+    }
+
+    /// Marks the pending entry `id` complete once the first `written` of its changes are
+    /// written. The files the apply did not get to are left out of it, and an entry with
+    /// none is dropped.
+    pub fn complete(&mut self, id: &str, written: usize) -> Result<(), AppError> {
+        let Some(index) = self.entries.iter().position(|entry| entry.id == id) else {
+            return Ok(());
+        };
+        if written == 0 {
+            let dropped = self.entries.remove(index);
+            self.remove_backups(&dropped)?;
+        } else {
+            let entry = &mut self.entries[index];
+            entry.files.truncate(written);
+            entry.pending = false;
+        }
+        self.save()
+    }
+
+    /// Restores the files of the most recent entry as they were before its apply,
+    /// then drops the entry. Returns `None` if the journal is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::UndoWouldLoseChanges`, leaving every file and the entry as they
+    /// are, if a file was changed after the apply, unless `discard_changes` is given.
+    pub fn undo_last(&mut self, discard_changes: bool) -> Result<Option<JournalEntry>, AppError> {
+        let Some(entry) = self.entries.last() else {
+            return Ok(None);
+        };
+        if !discard_changes {
+            let changed = changed_since(entry, &self.dir.join(&entry.id))?;
+            if !changed.is_empty() {
+                return Err(AppError::UndoWouldLoseChanges(changed));
+            }
+        }
+        let entry = self.entries.pop().expect("checked above");
+        let entry_dir = self.dir.join(&entry.id);
+
+        // Files are restored in reverse, in case one apply touched a file twice
+        for file in entry.files.iter().rev() {
+            match &file.backup {
+                Some(backup) => {
+                    let content = fs::read(entry_dir.join(backup))?;
+                    if let Some(parent) = file.path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    if discard_changes {
+                        atomic::write_forced(&file.path, &content)?;
+                    } else {
+                        atomic::write(&file.path, &content)?;
+                    }
+                }
+                None => match fs::remove_file(&file.path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(AppError::Io(e)),
+                    _ => {}
+                },
+            }
+        }
+
+        self.remove_backups(&entry)?;
+        self.save()?;
+        Ok(Some(entry))
+    }
+
+    fn remove_backups(&self, entry: &JournalEntry) -> Result<(), AppError> {
+        match fs::remove_dir_all(self.dir.join(&entry.id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(AppError::Io(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The files of `entry` whose content is no longer what the apply wrote. A file the
+/// apply touched twice is compared with its last write. A pending apply may not have
+/// reached a file, so one still as it was before the apply counts as unchanged too.
+fn changed_since(entry: &JournalEntry, entry_dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut checked = Vec::new();
+    let mut changed = Vec::new();
+    for file in entry.files.iter().rev() {
+        if checked.contains(&file.path) {
+            continue;
+        }
+        checked.push(file.path.clone());
+        let Some(written) = &file.written else {
+            continue;
+        };
+        match fs::read(&file.path) {
+            Ok(content) if digest(&content) == *written => {}
+            Ok(content) if entry.pending && is_backup(entry_dir, file, &content)? => {}
+            Ok(_) => changed.push(file.path.clone()),
+            // Deleted since: undoing restores the backup or has nothing to delete
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(AppError::Io(e)),
+        }
+    }
+    changed.reverse();
+    Ok(changed)
+}
+
+/// Whether `content` is the backup of `file`, i.e. what it held before the apply.
+fn is_backup(entry_dir: &Path, file: &JournalFile, content: &[u8]) -> Result<bool, AppError> {
+    match &file.backup {
+        Some(backup) => Ok(fs::read(entry_dir.join(backup))? == content),
+        None => Ok(false),
+    }
+}
+
+fn digest(content: &[u8]) -> String {
+    Sha256::digest(content).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_restores_modified_and_deletes_created_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join("journal");
+        fs::create_dir(&journal_dir).unwrap();
+        let modified = dir.path().join("main.rs");
+        let created = dir.path().join("new.rs");
+        fs::write(&modified, "fn main() {}\n").unwrap();
+
+        let mut journal = Journal::load_from(journal_dir.clone()).unwrap();
+        let changes = [
+            Change {
+                path: &modified,
+                action: "append",
+                before: Some(b"fn main() {}\n"),
+                after: b"fn main() {}\nfn helper() {}\n",
+            },
+            Change { path: &created, action: "create", before: None, after: b"mod x;\n" },
+        ];
+        let id = journal.begin("helpers", Some("append".to_string()), &changes).unwrap();
+        fs::write(&modified, "fn main() {}\nfn helper() {}\n").unwrap();
+        fs::write(&created, "mod x;\n").unwrap();
+        journal.complete(&id, changes.len()).unwrap();
+
+        // The entry survives a reload
+        let mut journal = Journal::load_from(journal_dir.clone()).unwrap();
+        assert_eq!(journal.entries.len(), 1);
+        assert_eq!(journal.entries[0].strategy.as_deref(), Some("append"));
+        assert!(!journal.entries[0].pending);
+
+        let undone = journal.undo_last(false).unwrap().unwrap();
+        assert_eq!(undone.template, "helpers");
+        assert_eq!(fs::read_to_string(&modified).unwrap(), "fn main() {}\n");
+        assert!(!created.exists());
+        assert!(!journal_dir.join(&undone.id).exists());
+        assert!(journal.undo_last(false).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_undo_refuses_to_lose_later_edits_unless_forced() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join("journal");
+        fs::create_dir(&journal_dir).unwrap();
+        let created = dir.path().join("new.rs");
+
+        let mut journal = Journal::load_from(journal_dir).unwrap();
+        let changes = [Change { path: &created, action: "create", before: None, after: b"mod x;\n" }];
+        let id = journal.begin("module", None, &changes).unwrap();
+        fs::write(&created, "mod x;\n").unwrap();
+        journal.complete(&id, 1).unwrap();
+        fs::write(&created, "mod x;\nmod edited_by_hand;\n").unwrap();
+
+        assert!(matches!(journal.undo_last(false), Err(AppError::UndoWouldLoseChanges(paths)) if paths.len() == 1));
+        assert!(created.exists());
+        assert_eq!(journal.entries.len(), 1);
+        journal.undo_last(true).unwrap().unwrap();
+        assert!(!created.exists());
+    }
+
+    #[test]
+    fn test_apply_cut_short_is_recorded_and_undone() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_dir = dir.path().join("journal");
+        fs::create_dir(&journal_dir).unwrap();
+        let first = dir.path().join("a.rs");
+        let second = dir.path().join("b.rs");
+        fs::write(&first, "// a\n").unwrap();
+        fs::write(&second, "// b\n").unwrap();
+
+        let mut journal = Journal::load_from(journal_dir.clone()).unwrap();
+        let changes = [
+            Change { path: &first, action: "append", before: Some(b"// a\n"), after: b"// a\nfn a() {}\n" },
+            Change { path: &second, action: "append", before: Some(b"// b\n"), after: b"// b\nfn b() {}\n" },
+        ];
+        journal.begin("fns", None, &changes).unwrap();
+        // The process dies after the first write: the entry stays pending
+        fs::write(&first, "// a\nfn a() {}\n").unwrap();
+
+        let mut journal = Journal::load_from(journal_dir).unwrap();
+        assert!(journal.entries[0].pending);
+        journal.undo_last(false).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&first).unwrap(), "// a\n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "// b\n");
+    }
+}
@@ -0,0 +1,32 @@
+use crate::config::{self, LoadedLayer};
+use crate::frontmatter;
+use crate::template::Loader;
+use std::fs;
+
+/// Loads included templates from the template stores by name, local store first.
+pub struct StoreLoader<'a> {
+    layers: &'a [LoadedLayer],
+}
+
+impl<'a> StoreLoader<'a> {
+    pub fn new(layers: &'a [LoadedLayer]) -> Self {
+        StoreLoader { layers }
+    }
+}
+
+impl Loader for StoreLoader<'_> {
+    /// Returns the body of a stored template, without its front matter.
+    fn load(&self, name: &str) -> Result<Option<String>, String> {
+        let Some((layer, entry)) = config::find_template(self.layers, name) else {
+            return Ok(None);
+        };
+        if entry.is_directory() {
+            return Err(format!("template '{name}' holds a directory and cannot be included"));
+        }
+        let path = entry.stored_path(&layer.templates_dir);
+        let content = fs::read_to_string(&path).map_err(|e| format!("failed to read {path:?}: {e}"))?;
+        let (_, body) = frontmatter::split(&content)
+            .map_err(|reason| format!("invalid front matter in {path:?}: {reason}"))?;
+        Ok(Some(body.to_string()))
+    }
+}
@@ -0,0 +1,155 @@
+use crate::config::Store;
+use crate::error::AppError;
+use crate::output::OutputConfig;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long to wait for another tempo process to release the store, unless
+/// `TEMPO_LOCK_TIMEOUT` gives a number of seconds.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const TIMEOUT_ENV: &str = "TEMPO_LOCK_TIMEOUT";
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An advisory lock on the template store, held until dropped.
+///
+/// Every read-modify-write of the manifest or the journal happens under it, so
+/// concurrent tempo commands cannot lose each other's changes. The lock file names
+/// the process holding it, for the error other processes report.
+#[derive(Debug)]
+pub struct StoreLock {
+    _file: File,
+}
+
+/// Takes the store lock, waiting for another process to release it.
+///
+/// # Errors
+///
+/// Returns `AppError::StoreLocked` naming the holder if the lock is not released in time.
+pub fn acquire(store: &Store, output: &OutputConfig) -> Result<StoreLock, AppError> {
+    let timeout = match std::env::var(TIMEOUT_ENV) {
+        Ok(secs) => parse_timeout(&secs).unwrap_or_else(|| {
+            output.warn(format!(
+                "\t\tWarning: {TIMEOUT_ENV}={secs:?} is not a number of seconds; waiting {} seconds.",
+                DEFAULT_TIMEOUT.as_secs()
+            ));
+            DEFAULT_TIMEOUT
+        }),
+        Err(_) => DEFAULT_TIMEOUT,
+    };
+    acquire_at(&store.lock_path()?, timeout, output)
+}
+
+/// A timeout in seconds, e.g. `2.5`; `None` for anything else, negative, infinite and NaN
+/// values included.
+fn parse_timeout(secs: &str) -> Option<Duration> {
+    secs.trim().parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+/// Takes the lock at `path` only if no other process holds it, without waiting.
+pub fn try_acquire(path: &Path) -> io::Result<Option<StoreLock>> {
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+    match file.try_lock() {
+        Ok(()) => {
+            write_holder(&mut file)?;
+            Ok(Some(StoreLock { _file: file }))
+        }
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e),
+    }
+}
+
+fn acquire_at(path: &Path, timeout: Duration, output: &OutputConfig) -> Result<StoreLock, AppError> {
+    // Not truncated on open: until the lock is ours, the file names the current holder
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+    let started = Instant::now();
+    let mut announced = false;
+
+    loop {
+        match file.try_lock() {
+            Ok(()) => break,
+            Err(TryLockError::WouldBlock) => {
+                if started.elapsed() >= timeout {
+                    return Err(AppError::StoreLocked {
+                        path: path.to_path_buf(),
+                        holder: read_holder(&mut file),
+                        waited: timeout.as_secs_f64(),
+                    });
+                }
+                if !announced {
+                    output.verbose(format!(
+                        "\t\t[VERBOSE] Waiting for the store lock held by {}.",
+                        read_holder(&mut file)
+                    ));
+                    announced = true;
+                }
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(TryLockError::Error(e)) => return Err(AppError::Io(e)),
+        }
+    }
+
+    write_holder(&mut file)?;
+    Ok(StoreLock { _file: file })
+}
+
+/// Records this process in the lock file, e.g. `pid 4242: tempo add io io.rs`.
+fn write_holder(file: &mut File) -> io::Result<()> {
+    let command: Vec<String> = std::env::args()
+        .enumerate()
+        .map(|(i, arg)| if i == 0 { program_name(&arg) } else { arg })
+        .collect();
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "pid {}: {}", std::process::id(), command.join(" "))?;
+    file.sync_all()
+}
+
+fn read_holder(file: &mut File) -> String {
+    let mut holder = String::new();
+    match file.rewind().and_then(|()| file.read_to_string(&mut holder)) {
+        Ok(_) if !holder.trim().is_empty() => format!("process {}", holder.trim()),
+        _ => "another process".to_string(),
+    }
+}
+
+fn program_name(arg: &str) -> String {
+    PathBuf::from(arg)
+        .file_name()
+        .map_or_else(|| arg.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_second_lock_times_out_naming_the_holder() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tempo.lock");
+        let output = OutputConfig::new(false, true);
+
+        let held = acquire_at(&path, Duration::ZERO, &output).unwrap();
+        let err = acquire_at(&path, Duration::from_millis(150), &output).unwrap_err();
+        match err {
+            AppError::StoreLocked { holder, .. } => {
+                assert!(holder.starts_with(&format!("process pid {}:", std::process::id())), "{holder}")
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+
+        drop(held);
+        assert!(acquire_at(&path, Duration::ZERO, &output).is_ok());
+    }
+
+    #[test]
+    fn test_parse_timeout_rejects_what_is_not_a_duration() {
+        assert_eq!(parse_timeout(" 2.5 "), Some(Duration::from_millis(2500)));
+        assert_eq!(parse_timeout("0"), Some(Duration::ZERO));
+        for bad in ["-1", "inf", "NaN", "1e300", "ten", ""] {
+            assert_eq!(parse_timeout(bad), None, "{bad}");
+        }
+    }
+}
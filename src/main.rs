@@ -1,16 +1,35 @@
+mod atomic;
+mod bundle;
 mod cli;
 mod commands;
 mod config;
+mod context;
+mod diff;
 mod error;
+mod frontmatter;
+mod git;
+mod history;
+mod insert;
+mod journal;
+mod lock;
+mod loader;
+mod managed;
 mod output;
 mod manifest;
+mod namespace;
+mod prompt;
+mod scaffold;
+mod schema;
+mod template;
 
 use clap::Parser;
 use cli::{Cli, Commands};
 use colored::*;
 use commands::{add, apply, list, remove};
 
-use crate::commands::{edit, path_cmd, show};
+use crate::commands::{diff_cmd, doctor, edit, export, history_cmd, import, path_cmd, reindex, restore, show, store_cmd, sync, undo};
+use crate::config::Stores;
+use crate::error::AppError;
 use crate::output::OutputConfig;
 
 fn main() {
@@ -18,15 +37,33 @@ fn main() {
     let force_flag = cli_args.force;
     let output = OutputConfig::new(cli_args.verbose, cli_args.quiet);
 
-    let command_result = match cli_args.command {
-        Commands::Add(add_args) => add::run(&add_args, force_flag, &output),
-        Commands::Apply(apply_args) => apply::run(&apply_args, force_flag, &output),
-        Commands::List => list::run(&output),
-        Commands::Remove(remove_args) => remove::run(&remove_args, force_flag, &output),
-        Commands::Show(show_args) => show::run(&show_args, &output),
-        Commands::Edit(edit_args) => edit::run(&edit_args, &output),
-        Commands::Path(path_args) => path_cmd::run(&path_args, &output),
-    };
+    let command_result = Stores::resolve(cli_args.store.as_deref())
+        .map_err(AppError::from)
+        .and_then(|stores| {
+            output.verbose(format!("\t\t[VERBOSE] Using the template store at {:?}.", stores.global.root_path()));
+            if let Some(local) = &stores.local {
+                output.verbose(format!("\t\t[VERBOSE] Layering the project store at {:?} over it.", local.root_path()));
+            }
+            match cli_args.command {
+                Commands::Add(add_args) => add::run(&add_args, force_flag, &stores, &output),
+                Commands::Apply(apply_args) => apply::run(&apply_args, force_flag, &stores, &output),
+                Commands::List => list::run(&stores, &output),
+                Commands::Remove(remove_args) => remove::run(&remove_args, force_flag, &stores, &output),
+                Commands::Show(show_args) => show::run(&show_args, &stores, &output),
+                Commands::Edit(edit_args) => edit::run(&edit_args, &stores, &output),
+                Commands::Path(path_args) => path_cmd::run(&path_args, &stores, &output),
+                Commands::Undo(undo_args) => undo::run(&undo_args, force_flag, &stores, &output),
+                Commands::Doctor(doctor_args) => doctor::run(&doctor_args, &stores, &output),
+                Commands::Reindex(reindex_args) => reindex::run(&reindex_args, &stores, &output),
+                Commands::History(history_args) => history_cmd::run(&history_args, &stores, &output),
+                Commands::Restore(restore_args) => restore::run(&restore_args, &stores, &output),
+                Commands::Diff(diff_args) => diff_cmd::run(&diff_args, &stores, &output),
+                Commands::Store(store_args) => store_cmd::run(&store_args, &stores, &output),
+                Commands::Sync => sync::run(&stores, &output),
+                Commands::Export(export_args) => export::run(&export_args, force_flag, &stores, &output),
+                Commands::Import(import_args) => import::run(&import_args, &stores, &output),
+            }
+        });
 
     if let Err(err) = command_result {
         eprintln!("\t{} {}", "✖ Error:".red().bold(), err.to_string().red());
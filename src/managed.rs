@@ -0,0 +1,147 @@
+use std::ops::Range;
+use std::path::Path;
+
+const BEGIN: &str = "tempo:begin";
+const END: &str = "tempo:end";
+
+/// Line comment syntax (or block comment delimiters) of a file type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comment {
+    open: &'static str,
+    close: &'static str,
+}
+
+impl Comment {
+    const fn line(open: &'static str) -> Self {
+        Comment { open, close: "" }
+    }
+
+    const fn block(open: &'static str, close: &'static str) -> Self {
+        Comment { open, close }
+    }
+
+    fn wrap(&self, text: &str) -> String {
+        if self.close.is_empty() {
+            format!("{} {}", self.open, text)
+        } else {
+            format!("{} {} {}", self.open, text, self.close)
+        }
+    }
+}
+
+/// Picks the comment syntax for `dest_path` from its extension (or file name), falling
+/// back to the template's `language`, and to `#` when neither is known.
+pub fn comment_for(dest_path: &Path, language: Option<&str>) -> Comment {
+    let file_name = dest_path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let ext = dest_path.extension().and_then(|e| e.to_str()).unwrap_or(file_name);
+    comment_for_language(ext)
+        .or_else(|| language.and_then(comment_for_language))
+        .unwrap_or(Comment::line("#"))
+}
+
+fn comment_for_language(language: &str) -> Option<Comment> {
+    let comment = match language.to_lowercase().as_str() {
+        "rs" | "rust" | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "java" | "kt" | "kts" | "scala" | "go"
+        | "js" | "mjs" | "jsx" | "ts" | "tsx" | "cs" | "swift" | "dart" | "zig" | "proto" | "php" | "groovy" => {
+            Comment::line("//")
+        }
+        "py" | "python" | "sh" | "bash" | "zsh" | "fish" | "rb" | "ruby" | "pl" | "r" | "toml" | "yaml" | "yml"
+        | "conf" | "cmake" | "makefile" | "dockerfile" | "nix" | "ps1" | "tf" | "gitignore" => Comment::line("#"),
+        "sql" | "lua" | "hs" | "haskell" | "elm" | "ada" => Comment::line("--"),
+        "lisp" | "el" | "clj" | "scm" | "ini" | "asm" => Comment::line(";;"),
+        "tex" | "sty" | "erl" | "m" => Comment::line("%"),
+        "vim" => Comment::line("\""),
+        "html" | "htm" | "xml" | "svg" | "md" | "markdown" | "vue" => Comment::block("<!--", "-->"),
+        "css" | "scss" | "less" => Comment::block("/*", "*/"),
+        "ml" | "mli" => Comment::block("(*", "*)"),
+        _ => return None,
+    };
+    Some(comment)
+}
+
+/// Wraps rendered content in begin/end marker comments naming the template.
+pub fn wrap(content: &str, template_name: &str, comment: Comment) -> String {
+    let mut block = comment.wrap(&format!("{BEGIN} {template_name}"));
+    block.push('\n');
+    block.push_str(content);
+    if !content.is_empty() && !content.ends_with('\n') {
+        block.push('\n');
+    }
+    block.push_str(&comment.wrap(&format!("{END} {template_name}")));
+    block.push('\n');
+    block
+}
+
+/// Why the managed block of a template could not be located.
+#[derive(Debug, PartialEq)]
+pub enum BlockError {
+    /// A begin marker on this (1-based) line has no matching end marker.
+    Unterminated(usize),
+    /// Begin markers on these (1-based) lines.
+    Duplicated(Vec<usize>),
+}
+
+/// Finds the byte range of the template's managed block, marker lines included.
+pub fn find_block(existing: &str, template_name: &str) -> Result<Option<Range<usize>>, BlockError> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(usize, usize)> = None; // line number, byte offset
+    let mut offset = 0;
+
+    for (index, line) in existing.split_inclusive('\n').enumerate() {
+        match open {
+            None if is_marker(line, BEGIN, template_name) => open = Some((index + 1, offset)),
+            Some((line_number, start)) if is_marker(line, END, template_name) => {
+                blocks.push((line_number, start..offset + line.len()));
+                open = None;
+            }
+            _ => {}
+        }
+        offset += line.len();
+    }
+
+    if let Some((line_number, _)) = open {
+        return Err(BlockError::Unterminated(line_number));
+    }
+    match blocks.len() {
+        0 => Ok(None),
+        1 => Ok(blocks.pop().map(|(_, range)| range)),
+        _ => Err(BlockError::Duplicated(blocks.into_iter().map(|(line, _)| line).collect())),
+    }
+}
+
+/// Whether `line` holds `<keyword> <name>`, inside whatever comment syntax.
+fn is_marker(line: &str, keyword: &str, name: &str) -> bool {
+    line.match_indices(keyword).any(|(at, _)| {
+        let rest = &line[at + keyword.len()..];
+        rest.starts_with(char::is_whitespace) && rest.split_whitespace().next() == Some(name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_uses_destination_comment_syntax() {
+        let rust = comment_for(Path::new("src/main.rs"), None);
+        assert_eq!(wrap("fn a() {}", "helpers", rust), "// tempo:begin helpers\nfn a() {}\n// tempo:end helpers\n");
+
+        let html = comment_for(Path::new("index.html"), Some("rust"));
+        assert_eq!(wrap("", "nav", html), "<!-- tempo:begin nav -->\n<!-- tempo:end nav -->\n");
+
+        assert_eq!(comment_for(Path::new("Makefile"), None), Comment::line("#"));
+        assert_eq!(comment_for(Path::new("notes"), Some("sql")), Comment::line("--"));
+    }
+
+    #[test]
+    fn test_find_block_by_template_name() {
+        let existing = "a\n// tempo:begin io\nx\n// tempo:end io\n# tempo:begin io-fast\n# tempo:end io-fast\n";
+        let range = find_block(existing, "io").unwrap().unwrap();
+        assert_eq!(&existing[range], "// tempo:begin io\nx\n// tempo:end io\n");
+
+        assert_eq!(find_block("a\n", "io"), Ok(None));
+        assert_eq!(find_block("// tempo:begin io\n", "io"), Err(BlockError::Unterminated(1)));
+        let twice = "// tempo:begin io\n// tempo:end io\n// tempo:begin io\n// tempo:end io\n";
+        assert_eq!(find_block(twice, "io"), Err(BlockError::Duplicated(vec![1, 3])));
+    }
+}
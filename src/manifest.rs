@@ -1,7 +1,39 @@
+use crate::schema::VariableSpec;
 use chrono::{DateTime, Utc}; // For timestamps
 use serde::{Deserialize, Serialize}; // For SerDe
-use std::collections::HashMap;
-use std::path::PathBuf; 
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// How `tempo apply` writes into a destination file that already exists.
+///
+/// In TOML the plain strategies are strings (`"append"`) and the anchored ones are
+/// tables (`{ at-marker = "helpers" }`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WriteStrategy {
+    Overwrite,
+    Append,
+    Prepend,
+    /// Insert after the single line matching this regex.
+    After(String),
+    /// Insert before the single line matching this regex.
+    Before(String),
+    /// Insert at the `tempo:insert <name>` marker line.
+    AtMarker(String),
+}
+
+impl std::fmt::Display for WriteStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteStrategy::Overwrite => write!(f, "overwrite"),
+            WriteStrategy::Append => write!(f, "append"),
+            WriteStrategy::Prepend => write!(f, "prepend"),
+            WriteStrategy::After(pattern) => write!(f, "after '{pattern}'"),
+            WriteStrategy::Before(pattern) => write!(f, "before '{pattern}'"),
+            WriteStrategy::AtMarker(name) => write!(f, "at-marker '{name}'"),
+        }
+    }
+}
 
 /// Represents a single template entry in the manifest.
 #[derive(Serialize, Deserialize, Debug, Clone)] 
@@ -38,6 +70,44 @@ pub struct TemplateEntry {
     /// Optional: detected or specified language
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub language: Option<String>,
+
+    /// Optional: the write strategy `tempo apply` uses when none is given and the destination exists.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_strategy: Option<WriteStrategy>,
+
+    /// Optional: the variables the template expects, checked by `tempo apply`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<VariableSpec>,
+
+    /// Optional: the template this one extends with `{% extends %}`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+
+    /// Optional: for a template holding a directory, the paths of its files relative to
+    /// the stored directory, with `/` separators.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+
+    /// Optional: earlier contents of the template, oldest first, kept by `add --force`,
+    /// `edit` and `restore`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<Revision>,
+}
+
+/// An earlier content of a single-file template, kept in the store's object area.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revision {
+    /// The SHA-256 of the content, naming its file under `objects/`.
+    pub object: String,
+
+    /// Where the content was stored, which changes when `add --force` brings another extension.
+    pub filename_in_storage: String,
+
+    pub source_extension: String,
+
+    /// When the content was saved, i.e. the template's `updated_at` at the time.
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub saved_at: DateTime<Utc>,
 }
 
 impl TemplateEntry {
@@ -53,22 +123,146 @@ impl TemplateEntry {
             original_source_path: None, // Can be set during 'add'
             tags: Vec::new(),
             language: None,
+            default_strategy: None,
+            variables: Vec::new(),
+            extends: None,
+            files: Vec::new(),
+            history: Vec::new(),
         }
     }
+
+    /// Where the template is stored under `templates_dir`. Namespaced templates are kept in
+    /// a subdirectory per namespace, e.g. `templates/cp/segtree.cpp`.
+    pub fn stored_path(&self, templates_dir: &Path) -> PathBuf {
+        crate::scaffold::join(templates_dir, &self.filename_in_storage)
+    }
+
+    /// Whether the template holds a directory tree rather than a single file.
+    pub fn is_directory(&self) -> bool {
+        !self.files.is_empty()
+    }
+}
+
+/// The manifest format this version of tempo reads and writes. Older manifests are
+/// migrated on load; newer ones are refused rather than misread.
+///
+/// Version 0 is the original flat format: a table per template at the top level.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Store-wide settings, kept in the manifest's `[settings]` table.
+///
+/// None are defined yet. Keys written by a newer tempo are kept as they are, so saving the
+/// manifest with this version does not drop them.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub other: toml::Table,
+}
+
+impl Settings {
+    fn is_empty(&self) -> bool {
+        self.other.is_empty()
+    }
 }
 
 /// Represents the entire manifest.
-/// The HashMap key is the user-facing `template_name`.
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
-#[serde(transparent)] // Treat this struct as its inner field for SerDe
+/// The `templates` key is the user-facing `template_name`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Manifest {
+    /// The format version, `MANIFEST_VERSION` for every manifest this tempo writes.
+    pub version: u32,
+
+    #[serde(default, skip_serializing_if = "Settings::is_empty")]
+    pub settings: Settings,
+
+    #[serde(default)]
     pub templates: HashMap<String, TemplateEntry>,
 }
 
+/// Why a manifest document could not be read.
+#[derive(Debug)]
+pub enum FormatError {
+    Toml(toml::de::Error),
+    /// Written by a newer tempo, in this format version.
+    TooNew(i64),
+}
+
+/// Reads a manifest in any format up to `MANIFEST_VERSION`, migrating older ones.
+///
+/// Returns the manifest and, when it had to be migrated, the version it was in.
+pub fn parse(content: &str) -> Result<(Manifest, Option<u32>), FormatError> {
+    let document: toml::Table = toml::from_str(content).map_err(FormatError::Toml)?;
+    // A flat manifest may hold a template named `version`, but that is a table, not a number
+    let version = match document.get("version") {
+        Some(toml::Value::Integer(version)) => *version,
+        _ => 0,
+    };
+    if version > i64::from(MANIFEST_VERSION) {
+        return Err(FormatError::TooNew(version));
+    }
+
+    match version {
+        0 => {
+            let templates = toml::Value::Table(document).try_into().map_err(FormatError::Toml)?;
+            Ok((Manifest { templates, ..Manifest::new() }, Some(0)))
+        }
+        _ => {
+            let manifest: Manifest = toml::Value::Table(document).try_into().map_err(FormatError::Toml)?;
+            Ok((manifest, None))
+        }
+    }
+}
+
+/// Merges two manifests that both changed `base`, template by template, for `tempo sync`.
+///
+/// A template changed on both sides keeps the newer entry (by `updated_at`) with the
+/// revisions of both. A template missing on one side was removed there if the other
+/// side still has it as in `base`, and is kept otherwise. Settings of `ours` win.
+pub fn merge(base: &Manifest, ours: &Manifest, theirs: &Manifest) -> Manifest {
+    let mut merged = Manifest::new();
+    merged.settings.other = theirs.settings.other.clone();
+    merged.settings.other.extend(ours.settings.other.clone());
+
+    let names: HashSet<&String> = ours.templates.keys().chain(theirs.templates.keys()).collect();
+    for name in names {
+        let entry = match (ours.templates.get(name), theirs.templates.get(name)) {
+            (Some(ours), Some(theirs)) => {
+                let (newer, older) = if theirs.updated_at > ours.updated_at { (theirs, ours) } else { (ours, theirs) };
+                let mut entry = newer.clone();
+                for revision in &older.history {
+                    if !entry.history.contains(revision) {
+                        entry.history.push(revision.clone());
+                    }
+                }
+                entry.history.sort_by_key(|revision| revision.saved_at);
+                entry
+            }
+            (Some(only), None) | (None, Some(only)) => {
+                let unchanged = base.templates.get(name).is_some_and(|base| base.updated_at == only.updated_at);
+                if unchanged {
+                    continue;
+                }
+                only.clone()
+            }
+            (None, None) => continue,
+        };
+        merged.templates.insert(name.clone(), entry);
+    }
+    merged
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest::new()
+    }
+}
+
 impl Manifest {
     /// Creates an empty manifest.
     pub fn new() -> Self {
         Manifest {
+            version: MANIFEST_VERSION,
+            settings: Settings::default(),
             templates: HashMap::new(),
         }
     }
@@ -99,4 +293,43 @@ impl Manifest {
     pub fn get_template_mut(&mut self, name: &str) -> Option<&mut TemplateEntry> {
         self.templates.get_mut(name)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_merge_unions_templates_by_name() {
+        let mut base = Manifest::new();
+        for name in ["io", "old", "kept"] {
+            base.add_template(name.to_string(), TemplateEntry::new(format!("{name}.rs"), "rs".to_string()));
+        }
+
+        let (mut ours, mut theirs) = (base.clone(), base.clone());
+        // Ours edits `io` later than theirs does, and removes `old`
+        let io = ours.get_template_mut("io").unwrap();
+        io.description = Some("ours".to_string());
+        io.updated_at += Duration::seconds(20);
+        ours.remove_template("old");
+        ours.add_template("mine".to_string(), TemplateEntry::new("mine.rs".to_string(), "rs".to_string()));
+        let io = theirs.get_template_mut("io").unwrap();
+        io.description = Some("theirs".to_string());
+        io.updated_at += Duration::seconds(10);
+        io.history.push(Revision {
+            object: "ab".to_string(),
+            filename_in_storage: "io.rs".to_string(),
+            source_extension: "rs".to_string(),
+            saved_at: base.templates["io"].updated_at,
+        });
+        theirs.remove_template("kept");
+        theirs.add_template("yours".to_string(), TemplateEntry::new("yours.rs".to_string(), "rs".to_string()));
+
+        let merged = merge(&base, &ours, &theirs);
+        let mut names: Vec<&String> = merged.templates.keys().collect();
+        names.sort();
+        assert_eq!(names, ["io", "mine", "yours"]);
+        assert_eq!(merged.templates["io"].description.as_deref(), Some("ours"));
+        assert_eq!(merged.templates["io"].history.len(), 1);
+    }
+}
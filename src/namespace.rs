@@ -0,0 +1,85 @@
+/// Separates the namespaces of a template name, e.g. `cp/segtree` or `web/axum/handler`.
+pub const SEPARATOR: char = '/';
+
+/// Characters that cannot be part of a name, since each part becomes a file or directory name.
+const FORBIDDEN: &[char] = &['\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// A template name split into its namespace and its own name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TemplateName<'a> {
+    /// Everything before the last `/`, e.g. `cp` in `cp/segtree`; `None` for a plain name.
+    pub namespace: Option<&'a str>,
+    pub name: &'a str,
+}
+
+/// Parses a template name given on the command line.
+///
+/// # Errors
+///
+/// Returns the reason the name is invalid: an empty part, a `.` or `..` part, a part
+/// starting with `.` or with whitespace around it, or a character that cannot be used
+/// in a file name.
+pub fn parse(full_name: &str) -> Result<TemplateName<'_>, String> {
+    if full_name.trim().is_empty() {
+        return Err("Name cannot be empty".to_string());
+    }
+    for part in full_name.split(SEPARATOR) {
+        if part.trim().is_empty() {
+            return Err(format!("Namespaces and names cannot be empty (use e.g. 'cp{SEPARATOR}segtree')"));
+        }
+        if part == "." || part == ".." {
+            return Err(format!("'{part}' cannot be part of a name"));
+        }
+        if part.starts_with('.') {
+            return Err(format!("'{part}' cannot start with '.'"));
+        }
+        if part != part.trim() {
+            return Err(format!("'{part}' cannot start or end with whitespace"));
+        }
+        if part.chars().any(char::is_control) {
+            return Err("Name cannot contain control characters".to_string());
+        }
+        if let Some(c) = part.chars().find(|c| FORBIDDEN.contains(c)) {
+            return Err(format!("Name cannot contain '{c}'"));
+        }
+    }
+    Ok(split(full_name))
+}
+
+/// Splits an already valid name at its last `/`.
+pub fn split(full_name: &str) -> TemplateName<'_> {
+    match full_name.rsplit_once(SEPARATOR) {
+        Some((namespace, name)) => TemplateName { namespace: Some(namespace), name },
+        None => TemplateName { namespace: None, name: full_name },
+    }
+}
+
+/// Whether two stored paths would clash because one is a directory holding the other,
+/// e.g. a template stored as `cp` and the namespace directory of `cp/segtree.cpp`.
+pub fn storage_overlaps(a: &str, b: &str) -> bool {
+    let nested = |outer: &str, inner: &str| {
+        inner.strip_prefix(outer).is_some_and(|rest| rest.starts_with(SEPARATOR))
+    };
+    nested(a, b) || nested(b, a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_namespaced_names() {
+        assert_eq!(parse("io").unwrap(), TemplateName { namespace: None, name: "io" });
+        assert_eq!(
+            parse("web/axum/handler").unwrap(),
+            TemplateName { namespace: Some("web/axum"), name: "handler" }
+        );
+        for invalid in ["", "cp/", "/io", "cp//io", "cp/../io", "cp/.hidden", "a\\b", "cp/ io"] {
+            assert!(parse(invalid).is_err(), "{invalid:?} should be rejected");
+        }
+
+        assert!(storage_overlaps("cp", "cp/segtree.cpp"));
+        assert!(!storage_overlaps("cp.cpp", "cp/segtree.cpp"));
+        assert!(!storage_overlaps("cpp/a.rs", "cp/segtree.cpp"));
+    }
+}
@@ -0,0 +1,186 @@
+use crate::context;
+use crate::error::AppError;
+use crate::output::OutputConfig;
+use crate::schema::{self, VariableSpec};
+use crate::template::{self, Loader, MissingVariable, TemplateError, Value, Variables};
+use colored::*;
+use std::io::{self, Write};
+
+/// A variable to ask the user for, with its declaration from the template's schema if it has one.
+#[derive(Debug, Clone, Copy)]
+struct VariablePrompt<'a> {
+    name: &'a str,
+    spec: Option<&'a VariableSpec>,
+}
+
+impl VariablePrompt<'_> {
+    /// Checks an answer against the schema, if any, and converts it.
+    fn parse(&self, answer: &str) -> Result<Value, String> {
+        match self.spec {
+            Some(spec) => spec.parse_value(answer),
+            None => Ok(Value::from(answer)),
+        }
+    }
+}
+
+/// Prompting is only possible on an interactive terminal and outside quiet mode.
+pub fn can_prompt(output: &OutputConfig) -> bool {
+    !output.quiet && atty::is(atty::Stream::Stdin)
+}
+
+/// Renders `source`, asking on the terminal for any variable that has no value yet.
+///
+/// Values in `vars` are first checked against the template's declared `specs`, and
+/// defaults are filled in, so invalid input is rejected before anything is written.
+/// See `render_resolved` for the rendering itself.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidVariableValue` for a value that does not fit its declaration,
+/// and `AppError::MissingTemplateVariables` listing the missing names when prompting is
+/// not possible (quiet mode, or stdin is not a terminal).
+pub fn render_with_prompts(
+    template_name: &str,
+    source: &str,
+    specs: &[VariableSpec],
+    vars: &mut Variables,
+    loader: &dyn Loader,
+    output: &OutputConfig,
+) -> Result<String, AppError> {
+    resolve_declared(template_name, specs, vars, output)?;
+    render_resolved(template_name, source, specs, vars, loader, output)
+}
+
+/// Checks the values in `vars` against the declared `specs`, fills in defaults, and asks
+/// for required variables that have no value. Declaring a built-in variable fails with
+/// `AppError::BuiltinVariableDeclared`.
+pub fn resolve_declared(
+    template_name: &str,
+    specs: &[VariableSpec],
+    vars: &mut Variables,
+    output: &OutputConfig,
+) -> Result<(), AppError> {
+    // A built-in value would be checked against a declaration that was never meant for it
+    if let Some(spec) = specs.iter().find(|spec| context::BUILTIN_NAMES.contains(&spec.name.as_str())) {
+        return Err(AppError::BuiltinVariableDeclared {
+            name: template_name.to_string(),
+            variable: spec.name.clone(),
+        });
+    }
+    let resolution = schema::resolve(specs, vars).map_err(|invalid| AppError::InvalidVariableValue {
+        name: template_name.to_string(),
+        variable: invalid.variable,
+        value: invalid.value,
+        reason: invalid.reason,
+    })?;
+    if !resolution.missing.is_empty() {
+        let missing: Vec<MissingVariable> = resolution
+            .missing
+            .into_iter()
+            .map(MissingVariable::declared)
+            .collect();
+        ask_for(template_name, &missing, specs, vars, output)?;
+    }
+    Ok(())
+}
+
+/// Renders `source` with variables already checked by `resolve_declared`.
+///
+/// Asked values are added to `vars`. Rendering is retried after each round of
+/// questions, since a new value can enable a branch that uses further variables.
+/// Included templates are looked up through `loader`.
+pub fn render_resolved(
+    template_name: &str,
+    source: &str,
+    specs: &[VariableSpec],
+    vars: &mut Variables,
+    loader: &dyn Loader,
+    output: &OutputConfig,
+) -> Result<String, AppError> {
+    let parsed = template::parse(source).map_err(|e| AppError::from_template(template_name, e))?;
+    loop {
+        match parsed.render(template_name, vars, loader) {
+            Ok(rendered) => return Ok(rendered),
+            Err(TemplateError::MissingVariables(missing)) => {
+                ask_for(template_name, &missing, specs, vars, output)?;
+            }
+            Err(e) => return Err(AppError::from_template(template_name, e)),
+        }
+    }
+}
+
+/// Asks for each of the `missing` variables and adds the answers to `vars`.
+fn ask_for(
+    template_name: &str,
+    missing: &[MissingVariable],
+    specs: &[VariableSpec],
+    vars: &mut Variables,
+    output: &OutputConfig,
+) -> Result<(), AppError> {
+    let missing_error = || AppError::MissingTemplateVariables {
+        name: template_name.to_string(),
+        missing: missing.to_vec(),
+    };
+    if !can_prompt(output) {
+        return Err(missing_error());
+    }
+
+    eprintln!(
+        "\t{} Template '{}' needs some values:",
+        "?".cyan().bold(),
+        template_name.yellow()
+    );
+
+    for variable in missing {
+        let prompt = VariablePrompt {
+            name: &variable.name,
+            spec: specs.iter().find(|spec| spec.name == variable.name),
+        };
+        // `None` means stdin was closed; give up instead of asking forever
+        let value = ask(&prompt)?.ok_or_else(missing_error)?;
+        vars.insert(variable.name.clone(), value);
+    }
+    Ok(())
+}
+
+/// Asks for one variable until a valid answer is given.
+fn ask(prompt: &VariablePrompt<'_>) -> Result<Option<Value>, AppError> {
+    let default = prompt.spec.and_then(|spec| spec.default_text());
+
+    loop {
+        if let Some(description) = prompt.spec.and_then(|spec| spec.description.as_ref()) {
+            eprintln!("\t  {}", description.dimmed());
+        }
+        let mut label = format!("\t  {}", prompt.name.cyan().bold());
+        if let Some(spec) = prompt.spec
+            && !spec.choices.is_empty()
+        {
+            label.push_str(&format!(" [{}]", spec.choices.join("/")));
+        }
+        if let Some(default) = &default {
+            label.push_str(&format!(" ({})", format!("default: {default}").dimmed()));
+        }
+        eprint!("{label}: ");
+        io::stderr().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(None);
+        }
+        let input = input.trim_end_matches(['\r', '\n']);
+
+        let answer = match (&default, input.is_empty()) {
+            (Some(default), true) => default.as_str(),
+            (None, true) => {
+                eprintln!("\t  {}", "A value is required.".yellow());
+                continue;
+            }
+            (_, false) => input,
+        };
+
+        match prompt.parse(answer) {
+            Ok(value) => return Ok(Some(value)),
+            Err(reason) => eprintln!("\t  {}", reason.yellow()),
+        }
+    }
+}
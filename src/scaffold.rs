@@ -0,0 +1,282 @@
+use crate::error::AppError;
+use crate::output::OutputConfig;
+use crate::prompt;
+use crate::schema::VariableSpec;
+use crate::template::{Loader, Variables};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the file listing the paths to leave out of a directory template.
+pub const IGNORE_FILE: &str = ".tempoignore";
+
+/// Patterns that are always ignored when adding a directory.
+const DEFAULT_IGNORES: &[&str] = &[".git", "target/", IGNORE_FILE];
+
+/// One line of a `.tempoignore` file.
+#[derive(Debug, Clone, PartialEq)]
+struct IgnorePattern {
+    glob: String,
+    /// A pattern with a slash before its end is matched against the whole relative path,
+    /// anything else against each file or directory name.
+    anchored: bool,
+    /// Written with a trailing slash: matches directories only.
+    dir_only: bool,
+}
+
+impl IgnorePattern {
+    fn parse(line: &str) -> Option<Self> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (line, dir_only) = match line.strip_suffix('/') {
+            Some(stripped) => (stripped, true),
+            None => (line, false),
+        };
+        let anchored = line.contains('/');
+        Some(IgnorePattern {
+            glob: line.trim_start_matches('/').to_string(),
+            anchored,
+            dir_only,
+        })
+    }
+
+    fn matches(&self, relative_path: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let subject = if self.anchored {
+            relative_path
+        } else {
+            relative_path.rsplit('/').next().unwrap_or(relative_path)
+        };
+        glob_match(self.glob.as_bytes(), subject.as_bytes())
+    }
+}
+
+/// Matches `*` (anything but `/`), `**` (anything) and `?` (one character but `/`).
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        [b'*', b'*', rest @ ..] => (0..=text.len()).any(|i| glob_match(rest.strip_prefix(b"/").unwrap_or(rest), &text[i..])),
+        [b'*', rest @ ..] => {
+            let segment_end = text.iter().position(|&c| c == b'/').unwrap_or(text.len());
+            (0..=segment_end).any(|i| glob_match(rest, &text[i..]))
+        }
+        [b'?', rest @ ..] => matches!(text, [c, tail @ ..] if *c != b'/' && glob_match(rest, tail)),
+        [p, rest @ ..] => matches!(text, [c, tail @ ..] if c == p && glob_match(rest, tail)),
+    }
+}
+
+/// Lists the files of a directory template, relative to `root` with `/` separators and sorted.
+///
+/// `.git`, `target/` and the patterns in `root/.tempoignore` are left out.
+///
+/// # Errors
+///
+/// Returns `AppError::ReadDir` if part of the tree cannot be read.
+pub fn collect_files(root: &Path) -> Result<Vec<String>, AppError> {
+    let mut patterns: Vec<IgnorePattern> = DEFAULT_IGNORES.iter().filter_map(|p| IgnorePattern::parse(p)).collect();
+    if let Ok(ignore_file) = fs::read_to_string(root.join(IGNORE_FILE)) {
+        patterns.extend(ignore_file.lines().filter_map(IgnorePattern::parse));
+    }
+
+    let mut files = Vec::new();
+    walk(root, "", &patterns, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn walk(dir: &Path, prefix: &str, patterns: &[IgnorePattern], files: &mut Vec<String>) -> Result<(), AppError> {
+    let read_error = |e| AppError::ReadDir {
+        source_path: dir.to_path_buf(),
+        source_error: e,
+    };
+    for entry in fs::read_dir(dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue; // Stored paths are recorded in the manifest, so they must be valid UTF-8
+        };
+        let relative_path = format!("{prefix}{name}");
+        let is_dir = entry.file_type().map_err(read_error)?.is_dir();
+        if patterns.iter().any(|pattern| pattern.matches(&relative_path, is_dir)) {
+            continue;
+        }
+
+        if is_dir {
+            walk(&entry.path(), &format!("{relative_path}/"), patterns, files)?;
+        } else {
+            files.push(relative_path);
+        }
+    }
+    Ok(())
+}
+
+/// Joins a stored `/`-separated relative path onto `root`.
+pub fn join(root: &Path, relative_path: &str) -> PathBuf {
+    relative_path.split('/').fold(root.to_path_buf(), |path, part| path.join(part))
+}
+
+/// Renders a stored relative path written with template syntax, e.g. `src/{{ module }}/mod.rs`.
+///
+/// Returns `None` when the path or one of its components renders empty, which leaves the
+/// file out: `{% if with_bench %}benches{% endif %}/main.rs` is only written with `with_bench`.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRenderedPath` if a component renders to `..`, and the
+/// errors of `prompt::render_resolved` for the path itself.
+pub fn render_relative_path(
+    template_name: &str,
+    path: &str,
+    specs: &[VariableSpec],
+    vars: &mut Variables,
+    loader: &dyn Loader,
+    output: &OutputConfig,
+) -> Result<Option<String>, AppError> {
+    if !has_template_syntax(path) {
+        return Ok(Some(path.to_string()));
+    }
+    let label = format!("{template_name} (path '{path}')");
+    let rendered = prompt::render_resolved(&label, path, specs, vars, loader, output)?;
+
+    let components: Vec<&str> = rendered.split('/').map(str::trim).collect();
+    if components.iter().any(|component| component.is_empty()) {
+        return Ok(None);
+    }
+    if components.contains(&"..") {
+        return Err(AppError::InvalidRenderedPath {
+            name: template_name.to_string(),
+            path: rendered,
+            reason: "it would leave the destination directory".to_string(),
+        });
+    }
+    Ok(Some(components.join("/")))
+}
+
+/// Renders a destination path given on the command line, if it uses template syntax.
+///
+/// # Errors
+///
+/// Returns `AppError::InvalidRenderedPath` if the path renders empty.
+pub fn render_destination(
+    template_name: &str,
+    dest: &Path,
+    specs: &[VariableSpec],
+    vars: &mut Variables,
+    loader: &dyn Loader,
+    output: &OutputConfig,
+) -> Result<PathBuf, AppError> {
+    let Some(path) = dest.to_str().filter(|path| has_template_syntax(path)) else {
+        return Ok(dest.to_path_buf());
+    };
+    let label = format!("{template_name} (destination '{path}')");
+    let rendered = prompt::render_resolved(&label, path, specs, vars, loader, output)?;
+    if rendered.trim().is_empty() {
+        return Err(AppError::InvalidRenderedPath {
+            name: template_name.to_string(),
+            path: path.to_string(),
+            reason: "it renders empty".to_string(),
+        });
+    }
+    Ok(PathBuf::from(rendered.trim()))
+}
+
+fn has_template_syntax(path: &str) -> bool {
+    path.contains("{{") || path.contains("{%")
+}
+
+/// Copies the listed files from `from` to `to`, creating directories as needed.
+pub fn copy_files(from: &Path, to: &Path, files: &[String]) -> Result<(), AppError> {
+    for file in files {
+        let source = join(from, file);
+        let dest = join(to, file);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&source, &dest).map_err(|e| AppError::FileCopy {
+            from: source.clone(),
+            to: dest.clone(),
+            source_error: e,
+        })?;
+    }
+    Ok(())
+}
+
+/// Removes a stored template, whether it is a single file or a directory.
+pub fn remove_stored(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Removes the directories between `path` and `root` that are left empty, such as the
+/// directory of a namespace whose last template was removed.
+pub fn remove_empty_parents(path: &Path, root: &Path) {
+    for dir in path.ancestors().skip(1).take_while(|dir| *dir != root && dir.starts_with(root)) {
+        // Fails, and stops, at the first directory that still has entries
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_patterns() {
+        let target = IgnorePattern::parse("target/").unwrap();
+        assert!(target.matches("target", true));
+        assert!(target.matches("crates/a/target", true));
+        assert!(!target.matches("target", false));
+
+        let logs = IgnorePattern::parse("*.log").unwrap();
+        assert!(logs.matches("a/b/run.log", false));
+        assert!(!logs.matches("run.logs", false));
+
+        let anchored = IgnorePattern::parse("/docs/**/*.png").unwrap();
+        assert!(anchored.matches("docs/img/deep/x.png", false));
+        assert!(anchored.matches("docs/x.png", false));
+        assert!(!anchored.matches("src/docs/x.png", false));
+
+        assert_eq!(IgnorePattern::parse("# comment"), None);
+    }
+
+    #[test]
+    fn test_collect_files_applies_ignore_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for path in ["src/main.rs", "target/debug/app", ".git/HEAD", "notes.tmp", "benches/b.rs"] {
+            let path = join(root, path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        fs::write(root.join(IGNORE_FILE), "*.tmp\n").unwrap();
+
+        assert_eq!(collect_files(root).unwrap(), vec!["benches/b.rs", "src/main.rs"]);
+    }
+
+    struct NoIncludes;
+
+    impl Loader for NoIncludes {
+        fn load(&self, _name: &str) -> Result<Option<String>, String> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_render_relative_path_skips_empty_components() {
+        let output = OutputConfig::new(false, true);
+        let mut vars = Variables::from([("module".to_string(), "parser".into())]);
+        let mut render = |path: &str| render_relative_path("t", path, &[], &mut vars, &NoIncludes, &output);
+
+        assert_eq!(render("src/{{ module }}/mod.rs").unwrap().as_deref(), Some("src/parser/mod.rs"));
+        assert_eq!(render("{% if with_bench %}benches{% endif %}/b.rs").unwrap(), None);
+        assert!(render("{{ module }}/../../x").is_err());
+    }
+}
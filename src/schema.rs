@@ -0,0 +1,214 @@
+use crate::template::{Value, Variables};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The kind of value a template variable accepts.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum VariableType {
+    #[default]
+    String,
+    Int,
+    Bool,
+    /// One of `VariableSpec::choices`.
+    Choice,
+    /// A comma-separated list, e.g. `Add,Sub,Mul`.
+    List,
+}
+
+impl fmt::Display for VariableType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VariableType::String => "string",
+            VariableType::Int => "int",
+            VariableType::Bool => "bool",
+            VariableType::Choice => "choice",
+            VariableType::List => "list",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Declares a variable a template expects, as stored in the manifest:
+///
+/// ```toml
+/// [[my_template.variables]]
+/// name = "mode"
+/// type = "choice"
+/// choices = ["fast", "safe"]
+/// default = "safe"
+/// description = "Which IO setup to generate"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VariableSpec {
+    pub name: String,
+
+    #[serde(default, rename = "type")]
+    pub kind: VariableType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Used when no value is given, unless the variable is `required`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<toml::Value>,
+
+    /// A required variable must always be given explicitly; its default is only offered when prompting.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+
+    /// The allowed values of a `choice` variable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub choices: Vec<String>,
+
+    /// A regular expression every value (every item, for lists) must match in full.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+impl VariableSpec {
+    /// The default rendered as the text a user would type for it.
+    pub fn default_text(&self) -> Option<String> {
+        self.default.as_ref().map(|value| match value {
+            toml::Value::String(s) => s.clone(),
+            toml::Value::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    toml::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            other => other.to_string(),
+        })
+    }
+
+    /// Checks `input` against the declared type, choices and pattern, and converts it
+    /// into the value the template sees.
+    pub fn parse_value(&self, input: &str) -> Result<Value, String> {
+        let value = match self.kind {
+            VariableType::String => Value::Str(input.to_string()),
+            VariableType::Int => input
+                .trim()
+                .parse::<i64>()
+                .map(Value::Int)
+                .map_err(|_| format!("expected an integer, got '{input}'"))?,
+            VariableType::Bool => match input.trim().to_lowercase().as_str() {
+                "true" | "yes" | "y" | "on" | "1" => Value::Bool(true),
+                "false" | "no" | "n" | "off" | "0" => Value::Bool(false),
+                _ => return Err(format!("expected true or false, got '{input}'")),
+            },
+            VariableType::Choice => {
+                if !self.choices.iter().any(|choice| choice == input) {
+                    return Err(format!("expected one of {}, got '{input}'", self.choices.join(", ")));
+                }
+                Value::Str(input.to_string())
+            }
+            VariableType::List => Value::List(Value::from(input).items()),
+        };
+
+        if let Some(pattern) = &self.pattern {
+            let regex = Regex::new(&format!("^(?:{pattern})$"))
+                .map_err(|e| format!("the declared pattern '{pattern}' is not a valid regular expression: {e}"))?;
+            for item in value.items() {
+                let text = item.to_string();
+                if !regex.is_match(&text) {
+                    return Err(format!("'{text}' does not match the pattern '{pattern}'"));
+                }
+            }
+        }
+
+        Ok(value)
+    }
+}
+
+/// How a set of variables stands against a template's schema.
+#[derive(Debug, Default)]
+pub struct Resolution {
+    /// Declared variables that still need a value from the user.
+    pub missing: Vec<String>,
+}
+
+/// A value that failed validation against its `VariableSpec`.
+#[derive(Debug)]
+pub struct InvalidValue {
+    pub variable: String,
+    pub value: String,
+    pub reason: String,
+}
+
+/// Validates and converts the values in `vars` against `specs`, then fills in
+/// defaults for optional variables that were not given.
+///
+/// Values are taken from `vars` as given (typically strings from `--set`) and
+/// replaced by their typed form.
+pub fn resolve(specs: &[VariableSpec], vars: &mut Variables) -> Result<Resolution, InvalidValue> {
+    let mut resolution = Resolution::default();
+
+    for spec in specs {
+        match vars.get(&spec.name) {
+            Some(given) => {
+                let given = given.to_string();
+                let value = spec.parse_value(&given).map_err(|reason| InvalidValue {
+                    variable: spec.name.clone(),
+                    value: given.clone(),
+                    reason,
+                })?;
+                vars.insert(spec.name.clone(), value);
+            }
+            None => match spec.default_text() {
+                Some(default) if !spec.required => {
+                    let value = spec.parse_value(&default).map_err(|reason| InvalidValue {
+                        variable: spec.name.clone(),
+                        value: default.clone(),
+                        reason: format!("invalid default: {reason}"),
+                    })?;
+                    vars.insert(spec.name.clone(), value);
+                }
+                _ if spec.required => resolution.missing.push(spec.name.clone()),
+                _ => {}
+            },
+        }
+    }
+
+    Ok(resolution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(toml_src: &str) -> VariableSpec {
+        toml::from_str(toml_src).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_converts_types_and_applies_defaults() {
+        let specs = vec![
+            spec("name = \"n\"\ntype = \"int\""),
+            spec("name = \"debug\"\ntype = \"bool\"\ndefault = false"),
+            spec("name = \"ops\"\ntype = \"list\"\ndefault = [\"Add\", \"Sub\"]"),
+            spec("name = \"id\"\nrequired = true"),
+        ];
+        let mut vars = Variables::new();
+        vars.insert("n".into(), Value::from("42"));
+
+        let resolution = resolve(&specs, &mut vars).unwrap();
+        assert_eq!(vars["n"], Value::Int(42));
+        assert_eq!(vars["debug"], Value::Bool(false));
+        assert_eq!(vars["ops"], Value::List(vec![Value::from("Add"), Value::from("Sub")]));
+        assert_eq!(resolution.missing, vec!["id"]);
+    }
+
+    #[test]
+    fn test_parse_value_checks_choices_and_pattern() {
+        let mode = spec("name = \"mode\"\ntype = \"choice\"\nchoices = [\"fast\", \"safe\"]");
+        assert!(mode.parse_value("fast").is_ok());
+        assert!(mode.parse_value("slow").is_err());
+
+        let id = spec("name = \"id\"\npattern = \"[0-9]+[A-Z]\"");
+        assert!(id.parse_value("1234A").is_ok());
+        assert!(id.parse_value("x1234A").is_err());
+    }
+}
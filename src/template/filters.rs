@@ -0,0 +1,157 @@
+use super::Value;
+
+/// Filters available in templates, with the number of arguments each takes.
+const FILTERS: &[(&str, usize)] = &[
+    ("upper", 0),
+    ("lower", 0),
+    ("trim", 0),
+    ("capitalize", 0),
+    ("snake_case", 0),
+    ("SCREAMING_SNAKE_CASE", 0),
+    ("PascalCase", 0),
+    ("camelCase", 0),
+    ("kebab-case", 0),
+    ("length", 0),
+    ("join", 1),
+    ("default", 1),
+];
+
+/// Checks that `name` is a known filter called with the right number of arguments.
+pub fn check(name: &str, arg_count: usize) -> Result<(), String> {
+    match FILTERS.iter().find(|(filter, _)| *filter == name) {
+        Some((_, expected)) if *expected == arg_count => Ok(()),
+        Some((_, expected)) => Err(format!(
+            "filter '{name}' takes {expected} argument(s), but {arg_count} were given"
+        )),
+        None => {
+            let known: Vec<&str> = FILTERS.iter().map(|(filter, _)| *filter).collect();
+            Err(format!("unknown filter '{name}' (available: {})", known.join(", ")))
+        }
+    }
+}
+
+/// Applies a filter that has already been validated by `check`.
+/// `default` is handled by the renderer because it has to see undefined values.
+pub fn apply(name: &str, value: Value, args: &[Value]) -> Value {
+    let text = || value.to_string();
+    match name {
+        "upper" => Value::Str(text().to_uppercase()),
+        "lower" => Value::Str(text().to_lowercase()),
+        "trim" => Value::Str(text().trim().to_string()),
+        "capitalize" => {
+            let text = text();
+            let mut chars = text.chars();
+            Value::Str(match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            })
+        }
+        "snake_case" => Value::Str(join_words(&text(), "_", str::to_lowercase)),
+        "SCREAMING_SNAKE_CASE" => Value::Str(join_words(&text(), "_", str::to_uppercase)),
+        "kebab-case" => Value::Str(join_words(&text(), "-", str::to_lowercase)),
+        "PascalCase" => Value::Str(join_words(&text(), "", capitalize_word)),
+        "camelCase" => {
+            let words = split_words(&text());
+            let mut result = String::new();
+            for (i, word) in words.iter().enumerate() {
+                if i == 0 {
+                    result.push_str(&word.to_lowercase());
+                } else {
+                    result.push_str(&capitalize_word(word));
+                }
+            }
+            Value::Str(result)
+        }
+        "length" => {
+            let length = match &value {
+                Value::List(items) => items.len(),
+                other => other.to_string().chars().count(),
+            };
+            Value::Int(length as i64)
+        }
+        "join" => {
+            let separator = args.first().map(Value::to_string).unwrap_or_default();
+            Value::Str(
+                value
+                    .items()
+                    .iter()
+                    .map(Value::to_string)
+                    .collect::<Vec<_>>()
+                    .join(&separator),
+            )
+        }
+        _ => value,
+    }
+}
+
+fn join_words(text: &str, separator: &str, transform: impl Fn(&str) -> String) -> String {
+    split_words(text)
+        .iter()
+        .map(|word| transform(word))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn capitalize_word(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect(),
+        None => String::new(),
+    }
+}
+
+/// Splits an identifier-like string into words, on separators and case changes:
+/// `"HTTPServer_config-v2"` becomes `["HTTP", "Server", "config", "v2"]`.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let chars: Vec<char> = text.chars().collect();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if let Some(&prev) = current.chars().last().as_ref() {
+            let next = chars.get(i + 1).copied();
+            let lower_to_upper = (prev.is_lowercase() || prev.is_ascii_digit()) && c.is_uppercase();
+            // The last capital of an acronym starts a new word: "HTTPServer" -> "HTTP", "Server"
+            let acronym_end = prev.is_uppercase() && c.is_uppercase() && next.is_some_and(char::is_lowercase);
+            if lower_to_upper || acronym_end {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_str(name: &str, input: &str) -> String {
+        apply(name, Value::from(input), &[]).to_string()
+    }
+
+    #[test]
+    fn test_case_filters() {
+        assert_eq!(apply_str("snake_case", "HTTPServerConfig"), "http_server_config");
+        assert_eq!(apply_str("PascalCase", "segment tree"), "SegmentTree");
+        assert_eq!(apply_str("camelCase", "fast_io-reader"), "fastIoReader");
+        assert_eq!(apply_str("kebab-case", "AxumHandler"), "axum-handler");
+        assert_eq!(apply_str("SCREAMING_SNAKE_CASE", "maxN"), "MAX_N");
+    }
+
+    #[test]
+    fn test_check_rejects_unknown_filters_and_bad_arity() {
+        assert!(check("upper", 0).is_ok());
+        assert!(check("default", 0).is_err());
+        assert!(check("shout", 0).is_err());
+    }
+}
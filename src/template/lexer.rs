@@ -0,0 +1,165 @@
+use super::TemplateError;
+
+/// A top-level piece of template source.
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    /// Literal text, with escapes already resolved.
+    Text(String),
+    /// The inside of a `{{ ... }}` tag.
+    Expr { body: &'a str, offset: usize },
+    /// The inside of a `{% ... %}` tag.
+    Block { body: &'a str, offset: usize },
+}
+
+/// Splits template source into text, expression and block segments.
+///
+/// `{# ... #}` comments are dropped. A block or comment tag that is alone on its
+/// line takes the whole line with it, so control flow does not leave blank lines
+/// behind. `\{{`, `\{%` and `\{#` produce the literal delimiters, and everything
+/// between `{% raw %}` and `{% endraw %}` is kept verbatim.
+pub fn tokenize(source: &str) -> Result<Vec<Segment<'_>>, TemplateError> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    // Whether `text` started at the beginning of a line
+    let mut at_line_start = true;
+    let mut i = 0;
+
+    while i < source.len() {
+        let rest = &source[i..];
+
+        if rest.starts_with("\\{{") || rest.starts_with("\\{%") || rest.starts_with("\\{#") {
+            text.push_str(&rest[1..3]);
+            i += 3;
+            continue;
+        }
+
+        if rest.starts_with("{{") {
+            let end = find_close(source, i, "}}")?;
+            flush_text(&mut segments, &mut text);
+            segments.push(Segment::Expr {
+                body: &source[i + 2..end],
+                offset: i + 2,
+            });
+            at_line_start = false;
+            i = end + 2;
+            continue;
+        }
+
+        if rest.starts_with("{%") || rest.starts_with("{#") {
+            let is_comment = rest.starts_with("{#");
+            let end = find_close(source, i, if is_comment { "#}" } else { "%}" })?;
+            let body = &source[i + 2..end];
+            let mut next = end + 2;
+
+            let standalone = is_standalone(&text, at_line_start, &source[next..]);
+            if standalone {
+                let line_start = text.rfind('\n').map_or(0, |nl| nl + 1);
+                text.truncate(line_start);
+                next = source[next..].find('\n').map_or(source.len(), |nl| next + nl + 1);
+            }
+
+            if !is_comment && body.trim() == "raw" {
+                // Raw text joins the pending text, so `at_line_start` only changes if that was empty
+                if text.is_empty() {
+                    at_line_start = standalone;
+                }
+                let (raw, after) = take_raw(source, i, next)?;
+                text.push_str(raw);
+                i = after;
+                continue;
+            }
+            at_line_start = standalone;
+
+            flush_text(&mut segments, &mut text);
+            if !is_comment {
+                segments.push(Segment::Block { body, offset: i + 2 });
+            }
+            i = next;
+            continue;
+        }
+
+        let ch = rest.chars().next().unwrap_or_default();
+        text.push(ch);
+        i += ch.len_utf8();
+    }
+
+    flush_text(&mut segments, &mut text);
+    Ok(segments)
+}
+
+fn flush_text(segments: &mut Vec<Segment<'_>>, text: &mut String) {
+    if !text.is_empty() {
+        segments.push(Segment::Text(std::mem::take(text)));
+    }
+}
+
+/// Finds the closing delimiter of the tag opening at `open`.
+fn find_close(source: &str, open: usize, close: &str) -> Result<usize, TemplateError> {
+    source[open + 2..]
+        .find(close)
+        .map(|pos| open + 2 + pos)
+        .ok_or_else(|| {
+            let opening = &source[open..open + 2];
+            TemplateError::syntax(source, open, format!("'{opening}' is never closed with '{close}'"))
+        })
+}
+
+/// A tag is standalone when only whitespace surrounds it on its line.
+fn is_standalone(text_before: &str, at_line_start: bool, after: &str) -> bool {
+    let before_ok = match text_before.rfind('\n') {
+        Some(nl) => text_before[nl + 1..].chars().all(|c| c == ' ' || c == '\t'),
+        None => at_line_start && text_before.chars().all(|c| c == ' ' || c == '\t'),
+    };
+    let line_rest = after.split('\n').next().unwrap_or("");
+    before_ok && line_rest.trim_end_matches('\r').chars().all(|c| c == ' ' || c == '\t')
+}
+
+/// Returns the verbatim text of a `{% raw %}` section whose body starts at `body_start`,
+/// and the position just after its `{% endraw %}` tag.
+fn take_raw(source: &str, open: usize, body_start: usize) -> Result<(&str, usize), TemplateError> {
+    let mut search = body_start;
+    while let Some(pos) = source[search..].find("{%") {
+        let tag = search + pos;
+        let end = find_close(source, tag, "%}")?;
+        if source[tag + 2..end].trim() == "endraw" {
+            let mut after = end + 2;
+            let line_start = source[..tag].rfind('\n').map_or(0, |nl| nl + 1);
+            let mut raw_end = tag;
+            if source[line_start..tag].chars().all(|c| c == ' ' || c == '\t')
+                && is_standalone("", true, &source[after..])
+            {
+                raw_end = line_start;
+                after = source[after..].find('\n').map_or(source.len(), |nl| after + nl + 1);
+            }
+            return Ok((&source[body_start..raw_end], after));
+        }
+        search = end + 2;
+    }
+    Err(TemplateError::syntax(source, open, "'{% raw %}' is never closed with '{% endraw %}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_standalone_block_lines_are_removed() {
+        let segments = tokenize("a\n  {% if x %}\nb\n{% endif %}\nc").unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("a\n".into()),
+                Segment::Block { body: " if x ", offset: 6 },
+                Segment::Text("b\n".into()),
+                Segment::Block { body: " endif ", offset: 19 },
+                Segment::Text("c".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_section_is_verbatim() {
+        let segments = tokenize("{% raw %}\n{{ not_a_var }}\n{% endraw %}\n").unwrap();
+        assert_eq!(segments, vec![Segment::Text("{{ not_a_var }}\n".into())]);
+    }
+}
@@ -0,0 +1,338 @@
+//! The template language used by `tempo apply` and `tempo show --render`.
+//!
+//! - `{{ expr }}` outputs a value, e.g. `{{ name | PascalCase }}`.
+//! - `{% if cond %}`, `{% elif cond %}`, `{% else %}`, `{% endif %}` select text.
+//!   Conditions support `==`, `!=`, `and`, `or`, `not` and parentheses.
+//! - `{% for item in list %}` ... `{% endfor %}` repeats text; `loop.index`,
+//!   `loop.index0`, `loop.first` and `loop.last` are set inside the loop.
+//! - `{% include "name" %}` renders another stored template in place, with the same variables.
+//! - `{% extends "name" %}` renders the named template instead, with its `{% block x %}...{% endblock %}`
+//!   sections replaced by the blocks of the same name defined here.
+//! - `{# ... #}` is a comment, and `{% raw %}` ... `{% endraw %}` is copied verbatim.
+//! - `\{{`, `\{%` and `\{#` produce the literal delimiters.
+mod filters;
+mod lexer;
+mod parser;
+mod render;
+
+pub use parser::parse;
+
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+
+/// Values available to a template at render time, keyed by variable name.
+pub type Variables = HashMap<String, Value>;
+
+/// A value a template variable can hold.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+impl Value {
+    /// Empty strings and lists, `0`, `false` and the strings `"false"`, `"no"`,
+    /// `"off"` and `"0"` are falsy. Everything else is truthy.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Str(s) => !matches!(s.trim().to_lowercase().as_str(), "" | "false" | "no" | "off" | "0"),
+            Value::Int(n) => *n != 0,
+            Value::Bool(b) => *b,
+            Value::List(items) => !items.is_empty(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Value::Str(s) => s.is_empty(),
+            Value::List(items) => items.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The items a `for` loop iterates over. Strings are split on commas, so
+    /// `--set variants=Add,Sub,Mul` can drive a loop.
+    pub fn items(&self) -> Vec<Value> {
+        match self {
+            Value::List(items) => items.clone(),
+            Value::Str(s) => s
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(Value::from)
+                .collect(),
+            other => vec![other.clone()],
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Str(s) => f.write_str(s),
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::List(items) => {
+                let items: Vec<String> = items.iter().map(Value::to_string).collect();
+                f.write_str(&items.join(", "))
+            }
+        }
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Str(s)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Str(s.to_string())
+    }
+}
+
+/// Provides the source of other templates for `{% include %}`.
+pub trait Loader {
+    /// Returns the source of the template called `name`, or `None` if there is no such template.
+    fn load(&self, name: &str) -> Result<Option<String>, String>;
+}
+
+/// A variable without a value, and where the template first uses it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MissingVariable {
+    pub name: String,
+    /// Line and column of the first use; `None` for variables that are declared but not yet reached.
+    pub position: Option<(usize, usize)>,
+    /// The included or parent template the position refers to, when it is not the applied template itself.
+    pub included_from: Option<String>,
+}
+
+impl MissingVariable {
+    pub fn declared(name: String) -> Self {
+        MissingVariable {
+            name,
+            position: None,
+            included_from: None,
+        }
+    }
+}
+
+impl fmt::Display for MissingVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.position, &self.included_from) {
+            (Some((line, column)), Some(template)) => {
+                write!(f, "{} (line {line}, column {column} of '{template}')", self.name)
+            }
+            (Some((line, column)), None) => write!(f, "{} (line {line}, column {column})", self.name),
+            (None, _) => f.write_str(&self.name),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("Syntax error at line {line}, column {column}: {message}")]
+    Syntax {
+        message: String,
+        line: usize,
+        column: usize,
+    },
+
+    #[error("No value provided for: {}", join_missing(.0))]
+    MissingVariables(Vec<MissingVariable>), // in order of first use
+
+    #[error("Included template '{name}' not found (line {line}, column {column})")]
+    IncludeNotFound {
+        name: String,
+        line: usize,
+        column: usize,
+    },
+
+    #[error("Parent template '{name}' not found (line {line}, column {column})")]
+    ParentNotFound {
+        name: String,
+        line: usize,
+        column: usize,
+    },
+
+    #[error("Include cycle: {}", .0.join(" -> "))]
+    IncludeCycle(Vec<String>), // the chain of template names, ending with the repeated one
+
+    #[error("Inheritance cycle: {}", .0.join(" extends "))]
+    ExtendsCycle(Vec<String>), // from the rendered template up to the repeated one
+
+    #[error("In template '{name}': {source}")]
+    InTemplate {
+        name: String,
+        #[source]
+        source: Box<TemplateError>,
+    },
+
+    #[error("{0}")]
+    Load(String),
+}
+
+impl TemplateError {
+    /// Builds a syntax error located at byte `offset` of `source`.
+    fn syntax(source: &str, offset: usize, message: impl Into<String>) -> Self {
+        let (line, column) = line_col(source, offset);
+        TemplateError::Syntax {
+            message: message.into(),
+            line,
+            column,
+        }
+    }
+}
+
+/// Returns the name of the template `source` extends, if it has a valid `{% extends %}` tag.
+pub fn parent_of(source: &str) -> Option<String> {
+    parse(source).ok()?.parent().map(str::to_string)
+}
+
+pub fn join_missing(missing: &[MissingVariable]) -> String {
+    missing.iter().map(MissingVariable::to_string).collect::<Vec<_>>().join(", ")
+}
+
+/// Variable names may contain ASCII letters, digits, `_` and `-`, and must not start with a digit.
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Converts a byte offset into 1-based line and column numbers.
+fn line_col(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves includes from a fixed set of named sources.
+    struct MapLoader(HashMap<&'static str, &'static str>);
+
+    impl Loader for MapLoader {
+        fn load(&self, name: &str) -> Result<Option<String>, String> {
+            Ok(self.0.get(name).map(|source| source.to_string()))
+        }
+    }
+
+    fn render(source: &str, vars: &Variables) -> Result<String, TemplateError> {
+        parse(source)?.render("test", vars, &MapLoader(HashMap::new()))
+    }
+
+    fn vars(pairs: &[(&str, &str)]) -> Variables {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), Value::from(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_substitutes_placeholders() {
+        let vars = vars(&[("problem", "1234A"), ("author", "yz")]);
+        let out = render("// {{ problem }} by {{author}}\n", &vars);
+        assert_eq!(out.unwrap(), "// 1234A by yz\n");
+    }
+
+    #[test]
+    fn test_render_lists_all_missing_variables() {
+        let err = render("{{ a }} {{ b }}\n{{ a }} {{ c }}", &vars(&[("b", "x")])).unwrap_err();
+        match err {
+            TemplateError::MissingVariables(missing) => {
+                let names: Vec<&str> = missing.iter().map(|m| m.name.as_str()).collect();
+                assert_eq!(names, vec!["a", "c"]);
+                assert_eq!(missing[1].position, Some((2, 12)));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_render_escaped_braces_are_literal() {
+        let out = render(r"int a[2][2] = \{{1, 2}, {3, 4}};", &Variables::new());
+        assert_eq!(out.unwrap(), "int a[2][2] = {{1, 2}, {3, 4}};");
+    }
+
+    #[test]
+    fn test_render_if_else_with_flag() {
+        let source = "{% if derive_debug %}\n#[derive(Debug)]\n{% endif %}\nstruct {{ name | PascalCase }};\n";
+        let with = render(source, &vars(&[("derive_debug", "true"), ("name", "my_type")])).unwrap();
+        assert_eq!(with, "#[derive(Debug)]\nstruct MyType;\n");
+        let without = render(source, &vars(&[("name", "my_type")])).unwrap();
+        assert_eq!(without, "struct MyType;\n");
+    }
+
+    #[test]
+    fn test_render_for_loop_over_list() {
+        let source = "match op {\n{% for v in variants %}\n    Op::{{ v }} => {{ loop.index }},\n{% endfor %}\n}\n";
+        let out = render(source, &vars(&[("variants", "Add, Sub")])).unwrap();
+        assert_eq!(out, "match op {\n    Op::Add => 1,\n    Op::Sub => 2,\n}\n");
+    }
+
+    #[test]
+    fn test_render_default_filter_and_comparison() {
+        let source = "{{ author | default(\"anon\") | upper }}{% if mode == \"fast\" %}!{% endif %}";
+        assert_eq!(render(source, &vars(&[("mode", "fast")])).unwrap(), "ANON!");
+    }
+
+    #[test]
+    fn test_syntax_errors_carry_position() {
+        let err = render("ok\n{% if x %}\nno end", &Variables::new()).unwrap_err();
+        assert!(matches!(err, TemplateError::Syntax { line: 3, .. }), "{err}");
+
+        let err = render("a\n  {{ name | shout }}", &Variables::new()).unwrap_err();
+        assert!(matches!(err, TemplateError::Syntax { line: 2, column: 13, .. }), "{err}");
+    }
+
+    #[test]
+    fn test_include_renders_partial_with_same_variables() {
+        let loader = MapLoader(HashMap::from([("mod", "const MOD: i64 = {{ modulus }};\n")]));
+        let source = "{% include \"mod\" %}\nfn main() {}\n";
+        let out = parse(source)
+            .unwrap()
+            .render("main", &vars(&[("modulus", "998244353")]), &loader)
+            .unwrap();
+        assert_eq!(out, "const MOD: i64 = 998244353;\nfn main() {}\n");
+    }
+
+    #[test]
+    fn test_include_cycle_reports_chain() {
+        let loader = MapLoader(HashMap::from([("a", "{% include \"b\" %}"), ("b", "{% include \"a\" %}")]));
+        let err = parse("{% include \"a\" %}")
+            .unwrap()
+            .render("root", &Variables::new(), &loader)
+            .unwrap_err();
+        match err {
+            TemplateError::IncludeCycle(chain) => assert_eq!(chain, vec!["root", "a", "b", "a"]),
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_extends_replaces_overridden_blocks() {
+        let loader = MapLoader(HashMap::from([(
+            "cpp-base",
+            "#include <bits/stdc++.h>\n{% block solve %}void solve() {}\n{% endblock %}int main() { solve(); }\n",
+        )]));
+        let child = parse("{% extends \"cpp-base\" %}\n{% block solve %}\nvoid solve() { {{ body }} }\n{% endblock solve %}\n").unwrap();
+        assert_eq!(child.parent(), Some("cpp-base"));
+
+        let out = child.render("child", &vars(&[("body", "puts(\"hi\");")]), &loader).unwrap();
+        assert_eq!(out, "#include <bits/stdc++.h>\nvoid solve() { puts(\"hi\"); }\nint main() { solve(); }\n");
+
+        let err = parse("{% extends \"child\" %}").unwrap().render("child", &Variables::new(), &loader);
+        assert!(matches!(err, Err(TemplateError::ExtendsCycle(chain)) if chain == ["child", "child"]));
+    }
+}
@@ -0,0 +1,623 @@
+use super::filters;
+use super::lexer::{self, Segment};
+use super::{TemplateError, Value};
+use std::collections::HashSet;
+
+/// A parsed template, ready to be rendered any number of times.
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub(super) nodes: Vec<Node>,
+    pub(super) extends: Option<Parent>,
+}
+
+/// The template named by `{% extends "name" %}`, and where the tag is.
+#[derive(Debug, Clone)]
+pub(super) struct Parent {
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Template {
+    /// Returns the name of the template this one extends, if any.
+    pub fn parent(&self) -> Option<&str> {
+        self.extends.as_ref().map(|parent| parent.name.as_str())
+    }
+
+    /// Finds the body of the block called `name`, wherever it is nested.
+    pub(super) fn find_block(&self, name: &str) -> Option<&[Node]> {
+        find_block(&self.nodes, name)
+    }
+}
+
+fn find_block<'n>(nodes: &'n [Node], name: &str) -> Option<&'n [Node]> {
+    nodes.iter().find_map(|node| match node {
+        Node::Block { name: block_name, body } if block_name == name => Some(body.as_slice()),
+        Node::Block { body, .. } | Node::For { body, .. } => find_block(body, name),
+        Node::If { branches, otherwise } => branches
+            .iter()
+            .find_map(|(_, body)| find_block(body, name))
+            .or_else(|| find_block(otherwise, name)),
+        _ => None,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Node {
+    Text(String),
+    Output(Expr),
+    If {
+        branches: Vec<(Expr, Vec<Node>)>,
+        otherwise: Vec<Node>,
+    },
+    For {
+        var: String,
+        iterable: Expr,
+        body: Vec<Node>,
+    },
+    /// `{% include "name" %}`: renders another stored template in place.
+    Include(Expr),
+    /// `{% block name %}`: a section that templates extending this one can replace.
+    Block {
+        name: String,
+        body: Vec<Node>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct Expr {
+    pub kind: ExprKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum ExprKind {
+    Var(String),
+    Literal(Value),
+    Filter {
+        input: Box<Expr>,
+        name: String,
+        args: Vec<Expr>,
+    },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare {
+        negated: bool,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+}
+
+/// Parses template source into a `Template`.
+pub fn parse(source: &str) -> Result<Template, TemplateError> {
+    let segments = lexer::tokenize(source)?;
+    let mut parser = Parser {
+        source,
+        segments: segments.into_iter(),
+        extends: None,
+        blocks: HashSet::new(),
+    };
+    let (nodes, end) = parser.parse_nodes(&[])?;
+    debug_assert!(end.is_none());
+    Ok(Template {
+        nodes,
+        extends: parser.extends,
+    })
+}
+
+/// The block tag that ended a run of nodes, e.g. `else` or `endfor`.
+struct EndTag<'a> {
+    keyword: &'a str,
+    rest: &'a str,
+    offset: usize,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    segments: std::vec::IntoIter<Segment<'a>>,
+    extends: Option<Parent>,
+    /// Names of the blocks defined so far; each may appear only once.
+    blocks: HashSet<String>,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses nodes until one of the `terminators` block tags (or the end of input, when
+    /// `terminators` is empty) and returns them with the tag that stopped the run.
+    fn parse_nodes(&mut self, terminators: &[&str]) -> Result<(Vec<Node>, Option<EndTag<'a>>), TemplateError> {
+        let mut nodes = Vec::new();
+
+        while let Some(segment) = self.segments.next() {
+            match segment {
+                Segment::Text(text) => nodes.push(Node::Text(text)),
+                Segment::Expr { body, offset } => {
+                    let expr = self.parse_expr_body(body, offset)?;
+                    nodes.push(Node::Output(expr));
+                }
+                Segment::Block { body, offset } => {
+                    let trimmed_start = body.len() - body.trim_start().len();
+                    let body_trimmed = body.trim();
+                    let offset = offset + trimmed_start;
+                    let (keyword, rest) = match body_trimmed.split_once(char::is_whitespace) {
+                        Some((k, r)) => (k, r),
+                        None => (body_trimmed, ""),
+                    };
+                    let rest_offset = offset + (body_trimmed.len() - rest.len());
+
+                    if terminators.contains(&keyword) {
+                        return Ok((nodes, Some(EndTag { keyword, rest, offset: rest_offset })));
+                    }
+
+                    match keyword {
+                        "if" => nodes.push(self.parse_if(rest, rest_offset)?),
+                        "for" => nodes.push(self.parse_for(rest, rest_offset)?),
+                        "include" => nodes.push(Node::Include(self.parse_expr_body(rest, rest_offset)?)),
+                        "extends" if terminators.is_empty() => self.parse_extends(rest, rest_offset)?,
+                        "block" => nodes.push(self.parse_block(rest, rest_offset)?),
+                        "" => return Err(TemplateError::syntax(self.source, offset, "empty block tag")),
+                        other => {
+                            let message = if terminators.is_empty() {
+                                format!("unexpected '{other}' block")
+                            } else {
+                                format!("unexpected '{other}' block, expected {}", expected(terminators))
+                            };
+                            return Err(TemplateError::syntax(self.source, offset, message));
+                        }
+                    }
+                }
+            }
+        }
+
+        if terminators.is_empty() {
+            Ok((nodes, None))
+        } else {
+            Err(TemplateError::syntax(
+                self.source,
+                self.source.len(),
+                format!("unexpected end of template, expected {}", expected(terminators)),
+            ))
+        }
+    }
+
+    fn parse_if(&mut self, condition: &'a str, offset: usize) -> Result<Node, TemplateError> {
+        let mut branches = Vec::new();
+        let mut otherwise = Vec::new();
+        let mut condition = self.parse_expr_body(condition, offset)?;
+
+        loop {
+            let (body, end) = self.parse_nodes(&["elif", "else", "endif"])?;
+            let end = end.expect("terminators are non-empty");
+            branches.push((condition, body));
+            match end.keyword {
+                "elif" => condition = self.parse_expr_body(end.rest, end.offset)?,
+                "else" => {
+                    self.expect_empty(&end)?;
+                    let (body, end) = self.parse_nodes(&["endif"])?;
+                    self.expect_empty(&end.expect("terminators are non-empty"))?;
+                    otherwise = body;
+                    break;
+                }
+                _ => {
+                    self.expect_empty(&end)?;
+                    break;
+                }
+            }
+        }
+
+        Ok(Node::If { branches, otherwise })
+    }
+
+    fn parse_for(&mut self, header: &'a str, offset: usize) -> Result<Node, TemplateError> {
+        let (var, iterable) = match header.split_once(" in ") {
+            Some((var, iterable)) => (var.trim(), iterable),
+            None => {
+                return Err(TemplateError::syntax(
+                    self.source,
+                    offset,
+                    "expected '{% for <name> in <expression> %}'",
+                ));
+            }
+        };
+        if !super::is_valid_name(var) {
+            return Err(TemplateError::syntax(self.source, offset, format!("'{var}' is not a valid loop variable name")));
+        }
+        let iterable_offset = offset + (header.len() - iterable.len());
+        let iterable = self.parse_expr_body(iterable, iterable_offset)?;
+
+        let (body, end) = self.parse_nodes(&["endfor"])?;
+        self.expect_empty(&end.expect("terminators are non-empty"))?;
+
+        Ok(Node::For {
+            var: var.to_string(),
+            iterable,
+            body,
+        })
+    }
+
+    fn parse_extends(&mut self, name: &'a str, offset: usize) -> Result<(), TemplateError> {
+        if self.extends.is_some() {
+            return Err(TemplateError::syntax(self.source, offset, "a template can extend only one template"));
+        }
+        let expr = self.parse_expr_body(name, offset)?;
+        let ExprKind::Literal(Value::Str(name)) = expr.kind else {
+            return Err(TemplateError::syntax(
+                self.source,
+                offset,
+                "expected '{% extends \"<template name>\" %}'",
+            ));
+        };
+        self.extends = Some(Parent {
+            name,
+            line: expr.line,
+            column: expr.column,
+        });
+        Ok(())
+    }
+
+    fn parse_block(&mut self, name: &'a str, offset: usize) -> Result<Node, TemplateError> {
+        let name = name.trim();
+        if !super::is_valid_name(name) {
+            return Err(TemplateError::syntax(self.source, offset, format!("'{name}' is not a valid block name")));
+        }
+        if !self.blocks.insert(name.to_string()) {
+            return Err(TemplateError::syntax(self.source, offset, format!("block '{name}' is defined twice")));
+        }
+
+        let (body, end) = self.parse_nodes(&["endblock"])?;
+        let end = end.expect("terminators are non-empty");
+        // `{% endblock %}` may repeat the block's name
+        let end_name = end.rest.trim();
+        if !end_name.is_empty() && end_name != name {
+            return Err(TemplateError::syntax(
+                self.source,
+                end.offset,
+                format!("'endblock {end_name}' closes block '{name}'"),
+            ));
+        }
+
+        Ok(Node::Block {
+            name: name.to_string(),
+            body,
+        })
+    }
+
+    fn expect_empty(&self, tag: &EndTag<'_>) -> Result<(), TemplateError> {
+        if tag.rest.trim().is_empty() {
+            Ok(())
+        } else {
+            Err(TemplateError::syntax(
+                self.source,
+                tag.offset,
+                format!("unexpected '{}' after '{}'", tag.rest.trim(), tag.keyword),
+            ))
+        }
+    }
+
+    /// Parses the full body of a tag as a single expression.
+    fn parse_expr_body(&self, body: &str, offset: usize) -> Result<Expr, TemplateError> {
+        let tokens = tokenize_expr(self.source, body, offset)?;
+        if tokens.is_empty() {
+            return Err(TemplateError::syntax(self.source, offset, "expected an expression"));
+        }
+        let mut parser = ExprParser {
+            source: self.source,
+            tokens,
+            pos: 0,
+            end_offset: offset + body.len(),
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(TemplateError::syntax(
+                self.source,
+                token.offset,
+                format!("unexpected {}", token.tok.describe()),
+            ));
+        }
+        Ok(expr)
+    }
+}
+
+fn expected(terminators: &[&str]) -> String {
+    terminators
+        .iter()
+        .map(|t| format!("'{{% {t} %}}'"))
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Pipe,
+    LParen,
+    RParen,
+    Comma,
+    EqEq,
+    NotEq,
+}
+
+impl Tok {
+    fn describe(&self) -> String {
+        match self {
+            Tok::Ident(name) => format!("'{name}'"),
+            Tok::Str(s) => format!("string \"{s}\""),
+            Tok::Int(n) => format!("number {n}"),
+            Tok::Pipe => "'|'".to_string(),
+            Tok::LParen => "'('".to_string(),
+            Tok::RParen => "')'".to_string(),
+            Tok::Comma => "','".to_string(),
+            Tok::EqEq => "'=='".to_string(),
+            Tok::NotEq => "'!='".to_string(),
+        }
+    }
+}
+
+struct Token {
+    tok: Tok,
+    offset: usize,
+}
+
+/// Splits the inside of a tag into expression tokens. `offset` is the position
+/// of `body` in `source`, used for error locations.
+fn tokenize_expr(source: &str, body: &str, offset: usize) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new();
+    let mut chars = body.char_indices().peekable();
+
+    while let Some(&(i, c)) = chars.peek() {
+        let at = offset + i;
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '|' => {
+                chars.next();
+                tokens.push(Token { tok: Tok::Pipe, offset: at });
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token { tok: Tok::LParen, offset: at });
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token { tok: Tok::RParen, offset: at });
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token { tok: Tok::Comma, offset: at });
+            }
+            '=' | '!' => {
+                chars.next();
+                match chars.next() {
+                    Some((_, '=')) => tokens.push(Token {
+                        tok: if c == '=' { Tok::EqEq } else { Tok::NotEq },
+                        offset: at,
+                    }),
+                    _ => return Err(TemplateError::syntax(source, at, format!("expected '{c}='"))),
+                }
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                let mut closed = false;
+                while let Some((_, ch)) = chars.next() {
+                    match ch {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(match escaped {
+                                    'n' => '\n',
+                                    't' => '\t',
+                                    other => other,
+                                });
+                            }
+                        }
+                        ch if ch == c => {
+                            closed = true;
+                            break;
+                        }
+                        ch => value.push(ch),
+                    }
+                }
+                if !closed {
+                    return Err(TemplateError::syntax(source, at, "unterminated string literal"));
+                }
+                tokens.push(Token { tok: Tok::Str(value), offset: at });
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut end = i;
+                while let Some(&(j, ch)) = chars.peek() {
+                    if ch.is_ascii_digit() || (j == i && ch == '-') {
+                        end = j + ch.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                let number = &body[i..end];
+                let value = number
+                    .parse::<i64>()
+                    .map_err(|_| TemplateError::syntax(source, at, format!("invalid number '{number}'")))?;
+                tokens.push(Token { tok: Tok::Int(value), offset: at });
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = i;
+                while let Some(&(j, ch)) = chars.peek() {
+                    if ch.is_ascii_alphanumeric() || ch == '_' || ch == '-' || ch == '.' {
+                        end = j + ch.len_utf8();
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token {
+                    tok: Tok::Ident(body[i..end].to_string()),
+                    offset: at,
+                });
+            }
+            other => {
+                return Err(TemplateError::syntax(source, at, format!("unexpected character '{other}'")));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct ExprParser<'s> {
+    source: &'s str,
+    tokens: Vec<Token>,
+    pos: usize,
+    end_offset: usize,
+}
+
+impl ExprParser<'_> {
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(name)) if name == keyword)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end_offset, |t| t.offset)
+    }
+
+    fn make(&self, kind: ExprKind, offset: usize) -> Expr {
+        let (line, column) = super::line_col(self.source, offset);
+        Expr { kind, line, column }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, TemplateError> {
+        let start = self.offset();
+        let mut lhs = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            let rhs = self.parse_and()?;
+            lhs = self.make(ExprKind::Or(Box::new(lhs), Box::new(rhs)), start);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, TemplateError> {
+        let start = self.offset();
+        let mut lhs = self.parse_not()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            let rhs = self.parse_not()?;
+            lhs = self.make(ExprKind::And(Box::new(lhs), Box::new(rhs)), start);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr, TemplateError> {
+        let start = self.offset();
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            let inner = self.parse_not()?;
+            return Ok(self.make(ExprKind::Not(Box::new(inner)), start));
+        }
+        self.parse_compare()
+    }
+
+    fn parse_compare(&mut self) -> Result<Expr, TemplateError> {
+        let start = self.offset();
+        let lhs = self.parse_filtered()?;
+        let negated = match self.peek() {
+            Some(Tok::EqEq) => false,
+            Some(Tok::NotEq) => true,
+            _ => return Ok(lhs),
+        };
+        self.pos += 1;
+        let rhs = self.parse_filtered()?;
+        Ok(self.make(
+            ExprKind::Compare {
+                negated,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            },
+            start,
+        ))
+    }
+
+    fn parse_filtered(&mut self) -> Result<Expr, TemplateError> {
+        let start = self.offset();
+        let mut expr = self.parse_primary()?;
+
+        while self.peek() == Some(&Tok::Pipe) {
+            self.pos += 1;
+            let filter_offset = self.offset();
+            let name = match self.peek() {
+                Some(Tok::Ident(name)) => name.clone(),
+                _ => return Err(self.unexpected("a filter name")),
+            };
+            self.pos += 1;
+
+            let mut args = Vec::new();
+            if self.peek() == Some(&Tok::LParen) {
+                self.pos += 1;
+                if self.peek() != Some(&Tok::RParen) {
+                    loop {
+                        args.push(self.parse_or()?);
+                        match self.peek() {
+                            Some(Tok::Comma) => self.pos += 1,
+                            Some(Tok::RParen) => break,
+                            _ => return Err(self.unexpected("',' or ')'")),
+                        }
+                    }
+                }
+                self.pos += 1; // ')'
+            }
+
+            filters::check(&name, args.len())
+                .map_err(|message| TemplateError::syntax(self.source, filter_offset, message))?;
+
+            expr = self.make(
+                ExprKind::Filter {
+                    input: Box::new(expr),
+                    name,
+                    args,
+                },
+                start,
+            );
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, TemplateError> {
+        let start = self.offset();
+        let kind = match self.peek().cloned() {
+            Some(Tok::Ident(name)) => match name.as_str() {
+                "true" => ExprKind::Literal(Value::Bool(true)),
+                "false" => ExprKind::Literal(Value::Bool(false)),
+                "and" | "or" | "not" => return Err(self.unexpected("an expression")),
+                _ => ExprKind::Var(name),
+            },
+            Some(Tok::Str(s)) => ExprKind::Literal(Value::Str(s)),
+            Some(Tok::Int(n)) => ExprKind::Literal(Value::Int(n)),
+            Some(Tok::LParen) => {
+                self.pos += 1;
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Tok::RParen) {
+                    return Err(self.unexpected("')'"));
+                }
+                self.pos += 1;
+                return Ok(inner);
+            }
+            _ => return Err(self.unexpected("an expression")),
+        };
+        self.pos += 1;
+        Ok(self.make(kind, start))
+    }
+
+    fn unexpected(&self, wanted: &str) -> TemplateError {
+        let found = match self.tokens.get(self.pos) {
+            Some(token) => token.tok.describe(),
+            None => "end of tag".to_string(),
+        };
+        TemplateError::syntax(self.source, self.offset(), format!("expected {wanted}, found {found}"))
+    }
+}
@@ -0,0 +1,272 @@
+use super::filters;
+use super::parser::{self, Expr, ExprKind, Node, Template};
+use super::{Loader, MissingVariable, TemplateError, Value, Variables};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+impl Template {
+    /// Renders the template with the given variables.
+    ///
+    /// Undefined variables are falsy in `if` conditions. Anywhere else they are
+    /// collected and reported together as `TemplateError::MissingVariables`.
+    /// `{% include %}` and `{% extends %}` tags are resolved through `loader`; `name`
+    /// is the name of this template, used to detect cycles.
+    pub fn render(&self, name: &str, vars: &Variables, loader: &dyn Loader) -> Result<String, TemplateError> {
+        let mut renderer = Renderer {
+            globals: vars,
+            scopes: Vec::new(),
+            missing: Vec::new(),
+            out: String::new(),
+            loader,
+            include_stack: vec![name.to_string()],
+            templates: HashMap::new(),
+            layers: Vec::new(),
+            current: name.to_string(),
+        };
+        renderer.render_template(name, Rc::new(self.clone()))?;
+
+        if !renderer.missing.is_empty() {
+            return Err(TemplateError::MissingVariables(renderer.missing));
+        }
+        Ok(renderer.out)
+    }
+}
+
+struct Renderer<'a> {
+    globals: &'a Variables,
+    /// Loop variables, innermost last.
+    scopes: Vec<Variables>,
+    missing: Vec<MissingVariable>,
+    out: String,
+    loader: &'a dyn Loader,
+    /// Names of the templates currently being included, outermost first.
+    include_stack: Vec<String>,
+    /// Templates loaded so far, by name.
+    templates: HashMap<String, Rc<Template>>,
+    /// The inheritance chain of the template being rendered, most derived first.
+    layers: Vec<(String, Rc<Template>)>,
+    /// Name of the template whose nodes are being rendered.
+    current: String,
+}
+
+impl Renderer<'_> {
+    /// Renders a template through its inheritance chain: the base template's nodes
+    /// are rendered, with each block taken from the most derived template defining it.
+    fn render_template(&mut self, name: &str, template: Rc<Template>) -> Result<(), TemplateError> {
+        let mut layers = vec![(name.to_string(), template)];
+        while let Some(parent) = layers.last().and_then(|(_, template)| template.extends.clone()) {
+            if layers.iter().any(|(layer, _)| *layer == parent.name) {
+                let mut chain: Vec<String> = layers.into_iter().map(|(layer, _)| layer).collect();
+                chain.push(parent.name);
+                return Err(TemplateError::ExtendsCycle(chain));
+            }
+            let template = self.load(&parent.name).map_err(|e| in_template(&parent.name, e))?;
+            let Some(template) = template else {
+                let err = TemplateError::ParentNotFound {
+                    name: parent.name,
+                    line: parent.line,
+                    column: parent.column,
+                };
+                // The position is in the template that names the parent
+                return Err(match layers.len() {
+                    1 => err,
+                    _ => in_template(&layers[layers.len() - 1].0, err),
+                });
+            };
+            layers.push((parent.name, template));
+        }
+
+        let (base_name, base) = layers.last().cloned().expect("layers start with the template itself");
+        let outer_layers = std::mem::replace(&mut self.layers, layers);
+        let outer_current = std::mem::replace(&mut self.current, base_name);
+        let result = self.render_nodes(&base.nodes);
+        self.layers = outer_layers;
+        self.current = outer_current;
+        result
+    }
+
+    fn render_nodes(&mut self, nodes: &[Node]) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => self.out.push_str(text),
+                Node::Output(expr) => match self.eval(expr) {
+                    Some(value) => self.out.push_str(&value.to_string()),
+                    None => self.record_missing(expr),
+                },
+                Node::If { branches, otherwise } => {
+                    let taken = branches
+                        .iter()
+                        .find(|(condition, _)| self.eval(condition).is_some_and(|v| v.is_truthy()));
+                    match taken {
+                        Some((_, body)) => self.render_nodes(body)?,
+                        None => self.render_nodes(otherwise)?,
+                    }
+                }
+                Node::For { var, iterable, body } => {
+                    let Some(value) = self.eval(iterable) else {
+                        self.record_missing(iterable);
+                        continue;
+                    };
+                    let items = value.items();
+                    let count = items.len();
+                    for (index, item) in items.into_iter().enumerate() {
+                        let mut scope = Variables::new();
+                        scope.insert(var.clone(), item);
+                        scope.insert("loop.index".into(), Value::Int(index as i64 + 1));
+                        scope.insert("loop.index0".into(), Value::Int(index as i64));
+                        scope.insert("loop.first".into(), Value::Bool(index == 0));
+                        scope.insert("loop.last".into(), Value::Bool(index + 1 == count));
+                        self.scopes.push(scope);
+                        let result = self.render_nodes(body);
+                        self.scopes.pop();
+                        result?;
+                    }
+                }
+                Node::Include(expr) => {
+                    let Some(name) = self.eval(expr) else {
+                        self.record_missing(expr);
+                        continue;
+                    };
+                    self.render_include(&name.to_string(), expr)?;
+                }
+                Node::Block { name, body } => self.render_block(name, body)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the most derived definition of a block, or `body` if no layer overrides it.
+    fn render_block(&mut self, name: &str, body: &[Node]) -> Result<(), TemplateError> {
+        let overriding = self
+            .layers
+            .iter()
+            .find(|(_, template)| template.find_block(name).is_some())
+            .cloned();
+        let Some((owner, template)) = overriding else {
+            return self.render_nodes(body);
+        };
+
+        let outer_current = std::mem::replace(&mut self.current, owner);
+        let result = self.render_nodes(template.find_block(name).unwrap_or(body));
+        self.current = outer_current;
+        result
+    }
+
+    fn render_include(&mut self, name: &str, expr: &Expr) -> Result<(), TemplateError> {
+        if self.include_stack.iter().any(|entry| entry == name) {
+            let mut chain = self.include_stack.clone();
+            chain.push(name.to_string());
+            return Err(TemplateError::IncludeCycle(chain));
+        }
+
+        let template = self
+            .load(name)
+            .map_err(|e| in_template(name, e))?
+            .ok_or_else(|| TemplateError::IncludeNotFound {
+                name: name.to_string(),
+                line: expr.line,
+                column: expr.column,
+            })?;
+
+        self.include_stack.push(name.to_string());
+        let result = self.render_template(name, template);
+        self.include_stack.pop();
+
+        result.map_err(|e| in_template(name, e))
+    }
+
+    /// Loads and parses a template by name, once per render.
+    fn load(&mut self, name: &str) -> Result<Option<Rc<Template>>, TemplateError> {
+        if let Some(template) = self.templates.get(name) {
+            return Ok(Some(Rc::clone(template)));
+        }
+        let Some(source) = self.loader.load(name).map_err(TemplateError::Load)? else {
+            return Ok(None);
+        };
+        let template = Rc::new(parser::parse(&source)?);
+        self.templates.insert(name.to_string(), Rc::clone(&template));
+        Ok(Some(template))
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .or_else(|| self.globals.get(name))
+    }
+
+    /// Evaluates an expression; `None` means it depends on an undefined variable.
+    fn eval(&self, expr: &Expr) -> Option<Value> {
+        match &expr.kind {
+            ExprKind::Var(name) => self.lookup(name).cloned(),
+            ExprKind::Literal(value) => Some(value.clone()),
+            ExprKind::Filter { input, name, args } => {
+                let input = self.eval(input);
+                if name == "default" {
+                    return match input {
+                        Some(value) if !value.is_empty() => Some(value),
+                        _ => self.eval(&args[0]),
+                    };
+                }
+                let args = args.iter().map(|arg| self.eval(arg)).collect::<Option<Vec<_>>>()?;
+                Some(filters::apply(name, input?, &args))
+            }
+            ExprKind::Not(inner) => Some(Value::Bool(!self.truthy(inner))),
+            ExprKind::And(lhs, rhs) => Some(Value::Bool(self.truthy(lhs) && self.truthy(rhs))),
+            ExprKind::Or(lhs, rhs) => Some(Value::Bool(self.truthy(lhs) || self.truthy(rhs))),
+            ExprKind::Compare { negated, lhs, rhs } => {
+                let equal = match (self.eval(lhs), self.eval(rhs)) {
+                    (Some(l), Some(r)) => l.to_string() == r.to_string(),
+                    _ => false,
+                };
+                Some(Value::Bool(equal != *negated))
+            }
+        }
+    }
+
+    fn truthy(&self, expr: &Expr) -> bool {
+        self.eval(expr).is_some_and(|v| v.is_truthy())
+    }
+
+    /// Records the undefined variable that made `expr` evaluate to nothing.
+    /// Only the first use of each name is kept.
+    fn record_missing(&mut self, expr: &Expr) {
+        if let Some(mut var) = self.first_undefined(expr)
+            && !self.missing.iter().any(|m| m.name == var.name)
+        {
+            if self.current != self.include_stack[0] {
+                var.included_from = Some(self.current.clone());
+            }
+            self.missing.push(var);
+        }
+    }
+
+    fn first_undefined(&self, expr: &Expr) -> Option<MissingVariable> {
+        match &expr.kind {
+            ExprKind::Var(name) if self.lookup(name).is_none() => Some(MissingVariable {
+                name: name.clone(),
+                position: Some((expr.line, expr.column)),
+                included_from: None,
+            }),
+            // Only the fallback can be responsible when `default` yields nothing
+            ExprKind::Filter { name, args, .. } if name == "default" => self.first_undefined(&args[0]),
+            ExprKind::Filter { input, args, .. } => self
+                .first_undefined(input)
+                .or_else(|| args.iter().find_map(|arg| self.first_undefined(arg))),
+            _ => None,
+        }
+    }
+}
+
+/// Attaches the name of the template an error happened in. Cycles are reported as a
+/// whole chain, and errors that already name their template are left as they are.
+fn in_template(name: &str, err: TemplateError) -> TemplateError {
+    match err {
+        TemplateError::IncludeCycle(_) | TemplateError::ExtendsCycle(_) | TemplateError::InTemplate { .. } => err,
+        other => TemplateError::InTemplate {
+            name: name.to_string(),
+            source: Box::new(other),
+        },
+    }
+}